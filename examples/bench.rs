@@ -0,0 +1,16 @@
+use anyhow::Result;
+use metrics_datadog_exporter::bench::{run, BenchConfig};
+
+fn main() -> Result<()> {
+    for gzip in [true, false] {
+        let config = BenchConfig {
+            counters: 10_000,
+            histograms: 1_000,
+            observations: 100,
+            gzip,
+        };
+        let report = run(&config)?;
+        println!("{config:?}\n{report:#?}");
+    }
+    Ok(())
+}
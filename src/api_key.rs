@@ -0,0 +1,33 @@
+//! API keys resolved at flush time
+
+use futures::future::{self, BoxFuture, FutureExt};
+
+use crate::Result;
+
+/// Source of the DataDog API key, consulted on every flush
+///
+/// Lets keys kept in a secrets manager rotate without restarting, and without the key living in
+/// the environment. A [`String`] is a static key.
+///
+/// ```
+/// use futures::future::{BoxFuture, FutureExt};
+/// use metrics_datadog_exporter::{DataDogApiKeyProvider, Result};
+///
+/// struct Vault;
+///
+/// impl DataDogApiKeyProvider for Vault {
+///     fn api_key(&self) -> BoxFuture<'_, Result<String>> {
+///         async { Ok("API_KEY".to_string()) }.boxed()
+///     }
+/// }
+/// ```
+pub trait DataDogApiKeyProvider: Send + Sync {
+    /// Current API key, caching it is up to the provider
+    fn api_key(&self) -> BoxFuture<'_, Result<String>>;
+}
+
+impl DataDogApiKeyProvider for String {
+    fn api_key(&self) -> BoxFuture<'_, Result<String>> {
+        future::ready(Ok(self.clone())).boxed()
+    }
+}
@@ -0,0 +1,109 @@
+//! Invariants checked between consecutive collections
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use metrics::Key;
+use parking_lot::Mutex;
+
+use crate::data::{DataDogMetric, DataDogMetricType};
+
+/// Invariant broken by a collected metric, passed to the callback of
+/// [`DataDogBuilder::flush_assertions`](crate::DataDogBuilder::flush_assertions)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataDogFlushViolation {
+    /// A count returned by a [`DataDogMetricSource`](crate::DataDogMetricSource) is lower than in
+    /// the previous flush with [`DataDogCounterTemporality::Cumulative`](crate::DataDogCounterTemporality::Cumulative),
+    /// where sources report running totals
+    CounterDecreased {
+        /// Metric name
+        metric: String,
+        /// Tags of the series
+        tags: Vec<String>,
+        /// Value in the previous flush
+        previous: u64,
+        /// Value in this flush
+        current: u64,
+    },
+    /// A gauge is outside of the bounds set with
+    /// [`DataDogBuilder::gauge_bounds`](crate::DataDogBuilder::gauge_bounds)
+    GaugeOutOfBounds {
+        /// Metric name
+        metric: String,
+        /// Tags of the series
+        tags: Vec<String>,
+        /// Value in this flush
+        value: f64,
+        /// Bounds of the gauge
+        bounds: RangeInclusive<f64>,
+    },
+}
+
+pub(crate) type ViolationCallback = Arc<dyn Fn(&DataDogFlushViolation) + Send + Sync>;
+
+/// Checks collected counters and gauges against the previous collection and their bounds
+pub(crate) struct FlushAssertions {
+    gauge_bounds: HashMap<String, RangeInclusive<f64>>,
+    callback: ViolationCallback,
+    /// Value of each sourced count in the last collection, by name and tags
+    counters: Mutex<HashMap<(String, Vec<String>), u64>>,
+}
+
+impl FlushAssertions {
+    pub(crate) fn new(
+        gauge_bounds: HashMap<String, RangeInclusive<f64>>,
+        callback: ViolationCallback,
+    ) -> Self {
+        FlushAssertions {
+            gauge_bounds,
+            callback,
+            counters: Mutex::default(),
+        }
+    }
+
+    /// Check that the running total `metric` returned by a source didn't decrease since the last
+    /// collection
+    ///
+    /// Registry counters can't decrease, [`Counter::absolute`](metrics::Counter::absolute) keeps
+    /// the highest value.
+    pub(crate) fn check_counter(&self, metric: &DataDogMetric) {
+        if metric.metric_type != DataDogMetricType::Count {
+            return;
+        }
+        let Some(current) = metric.points.iter().map(|p| p.as_f64() as u64).max() else {
+            return;
+        };
+        let key = (metric.metric.clone(), metric.tags.clone());
+        let previous = self.counters.lock().insert(key, current);
+        if let Some(previous) = previous.filter(|previous| current < *previous) {
+            (self.callback)(&DataDogFlushViolation::CounterDecreased {
+                metric: metric.metric.clone(),
+                tags: metric.tags.clone(),
+                previous,
+                current,
+            });
+        }
+    }
+
+    /// Check that the points of the gauge `key` are within its bounds, if any
+    pub(crate) fn check_gauge(&self, key: &Key, metric: &DataDogMetric) {
+        let Some(bounds) = self.gauge_bounds.get(key.name()) else {
+            return;
+        };
+        for value in metric.points.iter().map(|p| p.as_f64()) {
+            if !bounds.contains(&value) {
+                (self.callback)(&DataDogFlushViolation::GaugeOutOfBounds {
+                    metric: metric.metric.clone(),
+                    tags: metric.tags.clone(),
+                    value,
+                    bounds: bounds.clone(),
+                });
+            }
+        }
+    }
+
+    pub(crate) fn clear(&self) {
+        self.counters.lock().clear();
+    }
+}
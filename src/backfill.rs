@@ -0,0 +1,58 @@
+//! Submission of historical points
+
+use itertools::Itertools;
+use tracing::warn;
+
+use crate::clock::MAX_FUTURE_SECS;
+use crate::data::DataDogMetric;
+
+/// DataDog rejects points more than an hour in the past
+const MAX_PAST_SECS: i64 = 3600;
+
+/// Span of the timestamps submitted in a batch
+const WINDOW_SECS: i64 = 3600;
+
+/// Progress of [`DataDogExporter::backfill`], reported after each batch
+///
+/// [`DataDogExporter::backfill`]: crate::DataDogExporter::backfill
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataDogBackfillProgress {
+    /// Batches submitted so far
+    pub batches: usize,
+    /// Batches in the backfill
+    pub total_batches: usize,
+    /// Points submitted so far
+    pub points: usize,
+    /// Points skipped so far, being outside the window accepted by DataDog when their batch was
+    /// due
+    pub skipped_points: usize,
+}
+
+/// Metrics grouped by the hour of their timestamp, oldest first
+pub(crate) fn batches(metrics: Vec<DataDogMetric>) -> Vec<Vec<DataDogMetric>> {
+    metrics
+        .into_iter()
+        .into_group_map_by(|metric| metric.timestamp.div_euclid(WINDOW_SECS))
+        .into_iter()
+        .sorted_by_key(|(window, _)| *window)
+        .map(|(_, metrics)| metrics)
+        .collect()
+}
+
+/// Drop metrics DataDog would reject at `now`, returning the number of points dropped
+pub(crate) fn retain_accepted(metrics: &mut Vec<DataDogMetric>, now: i64) -> usize {
+    let mut skipped = 0;
+    metrics.retain(|metric| {
+        let accepted = (now - MAX_PAST_SECS..=now + MAX_FUTURE_SECS).contains(&metric.timestamp);
+        if !accepted {
+            warn!(
+                metric = %metric.metric,
+                timestamp = metric.timestamp,
+                "Skipping backfilled metric outside the accepted time window"
+            );
+            skipped += metric.points.len();
+        }
+        accepted
+    });
+    skipped
+}
@@ -0,0 +1,203 @@
+//! Synthetic load generation and pipeline timings
+//!
+//! Builds a registry of the requested shape and measures how long each stage of a flush takes,
+//! which helps sizing flush intervals and comparing configurations on the target hardware.
+//!
+//! [`generate`] emits sustained load with series churn through a recorder, to rehearse
+//! cardinality incidents against series budgets and flush settings before they happen for real.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use itertools::Itertools;
+use metrics::{counter, histogram, with_local_recorder, Recorder};
+use tokio::time::interval;
+
+use crate::data::{DataDogApiPost, DataDogSeries, DataDogSeriesTypePolicy};
+use crate::exporter::metric_requests;
+use crate::json;
+use crate::{DataDogBuilder, Result};
+
+/// Shape of the synthetic registry
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    /// Number of counters
+    pub counters: usize,
+    /// Number of histograms
+    pub histograms: usize,
+    /// Observations recorded by each histogram
+    pub observations: usize,
+    /// Compress request payloads
+    pub gzip: bool,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig {
+            counters: 1000,
+            histograms: 100,
+            observations: 100,
+            gzip: true,
+        }
+    }
+}
+
+/// Stage timings of a single flush
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// Time spent collecting metrics from the registry
+    pub collect: Duration,
+    /// Time spent serializing all series into a single JSON body
+    pub serialize: Duration,
+    /// Time spent compressing the single JSON body
+    pub compress: Duration,
+    /// Time spent building the request payloads, including chunking
+    pub requests: Duration,
+    /// Number of collected metrics
+    pub metrics: usize,
+    /// Number of request payloads
+    pub payloads: usize,
+    /// Total size of the request payloads
+    pub bytes: usize,
+}
+
+/// Populate a registry according to `config` and time each flush stage
+pub fn run(config: &BenchConfig) -> Result<BenchReport> {
+    let handle = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .gzip(config.gzip)
+        .build()?;
+
+    with_local_recorder(&handle.recorder, || {
+        for i in 0..config.counters {
+            counter!(format!("bench.counter.{i}")).increment(1);
+        }
+        for i in 0..config.histograms {
+            let histogram = histogram!(format!("bench.histogram.{i}"));
+            for o in 0..config.observations {
+                histogram.record(o as f64);
+            }
+        }
+    });
+
+    let start = Instant::now();
+    let metrics = handle.handle.collect();
+    let collect = start.elapsed();
+
+    let series = metrics
+        .iter()
+        .cloned()
+        .flat_map(DataDogSeries::new)
+        .collect_vec();
+
+    let start = Instant::now();
+    let body = json::to_vec(&DataDogApiPost { series: &series })?;
+    let serialize = start.elapsed();
+
+    let start = Instant::now();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&body)?;
+    encoder.finish()?;
+    let compress = start.elapsed();
+
+    let metric_count = metrics.len();
+    let start = Instant::now();
+    let payloads = metric_requests(metrics, &DataDogSeriesTypePolicy::default(), config.gzip)?;
+    let requests = start.elapsed();
+
+    Ok(BenchReport {
+        collect,
+        serialize,
+        compress,
+        requests,
+        metrics: metric_count,
+        payloads: payloads.len(),
+        bytes: payloads.iter().map(Vec::len).sum(),
+    })
+}
+
+/// Shape of the synthetic load emitted by [`generate`]
+#[derive(Debug, Clone)]
+pub struct LoadConfig {
+    /// Number of series live at any time
+    pub series: usize,
+    /// Points recorded per second across all live series
+    pub points_per_second: u64,
+    /// Fraction of live series replaced by new ones every second, between 0 and 1
+    pub churn: f64,
+    /// Name of the generated counter, series are told apart by a `series` tag
+    pub metric: String,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        LoadConfig {
+            series: 1000,
+            points_per_second: 10_000,
+            churn: 0.0,
+            metric: "bench.load".to_string(),
+        }
+    }
+}
+
+/// Load emitted by [`generate`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Number of points recorded
+    pub points: u64,
+    /// Number of distinct series created, including the ones retired by churn
+    pub series: u64,
+}
+
+const LOAD_TICK: Duration = Duration::from_millis(100);
+
+/// Record the load described by `config` for `duration`, through `recorder` or the global
+/// recorder if [`None`]
+pub async fn generate(
+    recorder: Option<&(dyn Recorder + Sync)>,
+    config: &LoadConfig,
+    duration: Duration,
+) -> LoadReport {
+    if config.series == 0 {
+        return LoadReport::default();
+    }
+    let ticks_per_second = Duration::from_secs(1).as_secs_f64() / LOAD_TICK.as_secs_f64();
+    let points_per_tick = config.points_per_second as f64 / ticks_per_second;
+    let churn_per_tick = config.series as f64 * config.churn.clamp(0.0, 1.0) / ticks_per_second;
+
+    // Live series are `first..first + config.series`, churn retires the oldest ones
+    let mut first = 0;
+    let (mut retired, mut owed) = (0.0, 0.0);
+    let mut next = 0;
+    let mut points = 0;
+    let mut ticks = interval(LOAD_TICK);
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        ticks.tick().await;
+        retired += churn_per_tick;
+        first = retired as usize;
+        owed += points_per_tick;
+        let due = owed as u64;
+        owed -= due as f64;
+
+        let mut record = || {
+            for _ in 0..due {
+                let series = first + next % config.series;
+                next += 1;
+                counter!(config.metric.clone(), "series" => series.to_string()).increment(1);
+            }
+        };
+        match recorder {
+            Some(recorder) => with_local_recorder(recorder, record),
+            None => record(),
+        }
+        points += due;
+    }
+
+    LoadReport {
+        points,
+        series: (first + config.series) as u64,
+    }
+}
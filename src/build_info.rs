@@ -0,0 +1,43 @@
+/// Tags identifying the build of the calling crate
+///
+/// Expands to a `Vec<(String, String)>` with
+/// - `version`: the crate version
+/// - `profile`: `debug` or `release`
+/// - `git_sha`: the `GIT_SHA` or `VERGEN_GIT_SHA` environment variable at compile time, if set
+/// - `rustc_version`: the `RUSTC_VERSION` or `VERGEN_RUSTC_SEMVER` environment variable at compile
+///   time, if set
+#[macro_export]
+macro_rules! build_info_tags {
+    () => {{
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        let mut tags = vec![
+            ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+            ("profile".to_string(), profile.to_string()),
+        ];
+        if let Some(sha) = option_env!("GIT_SHA").or(option_env!("VERGEN_GIT_SHA")) {
+            tags.push(("git_sha".to_string(), sha.to_string()));
+        }
+        if let Some(rustc) = option_env!("RUSTC_VERSION").or(option_env!("VERGEN_RUSTC_SEMVER")) {
+            tags.push(("rustc_version".to_string(), rustc.to_string()));
+        }
+        tags
+    }};
+}
+
+/// Add [`build_info_tags!`] of the calling crate to a [`DataDogBuilder`](crate::DataDogBuilder)
+///
+/// ```
+/// use metrics_datadog_exporter::{with_build_info, DataDogBuilder};
+///
+/// let builder = with_build_info!(DataDogBuilder::default());
+/// ```
+#[macro_export]
+macro_rules! with_build_info {
+    ($builder:expr) => {
+        $builder.add_tags($crate::build_info_tags!())
+    };
+}
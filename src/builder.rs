@@ -1,22 +1,122 @@
+use std::collections::HashMap;
+use std::env;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use std::time::Duration;
 
-use metrics::Label;
-use metrics_util::registry::{AtomicStorage, Registry};
+use metrics::{Key, Label};
+use metrics_util::registry::{GenerationalAtomicStorage, Registry};
 use reqwest::Client;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use reqwest::{Certificate, Identity};
+use tokio::runtime::Handle;
+use tracing::warn;
 
+use crate::api_key::DataDogApiKeyProvider;
+use crate::assertions::{DataDogFlushViolation, ViolationCallback};
+use crate::data::{
+    DataDogApiVersion, DataDogCounterTemporality, DataDogGaugeAggregation,
+    DataDogHistogramAggregate, DataDogMetric, DataDogSeriesTypePolicy, DataDogTagFilter,
+};
+use crate::destination::{ApiDestination, DataDogDestination};
+use crate::dogstatsd::DEFAULT_ADDR;
+#[cfg(unix)]
+use crate::dogstatsd::DEFAULT_SOCKET;
+use crate::empty::DataDogEmptyFlush;
 use crate::exporter::DataDogExporter;
+use crate::histograms::HistogramStores;
+use crate::jitter::DataDogJitter;
+use crate::latency::{DataDogLatencyAlert, DataDogLatencySlo, LatencyCallback};
+use crate::metadata::{DataDogMetadata, MetadataStore};
+use crate::negotiation::DataDogContentEncoding;
+use crate::preset::DataDogPreset;
+use crate::proxy::DataDogProxy;
+#[cfg(feature = "recorder")]
 use crate::recorder::DataDogRecorder;
-use crate::{DataDogHandle, Error};
+use crate::retry::DataDogRetryPolicy;
+use crate::shadow::ShadowCallback;
+use crate::signer::DataDogRequestSigner;
+use crate::sink::DataDogSink;
+use crate::site::DataDogSite;
+use crate::source::DataDogMetricSource;
+use crate::status::{DataDogDeliveryReport, DeliveryReportCallback};
+use crate::tags::{self, DataDogTags};
+use crate::template;
+#[cfg(feature = "recorder")]
+use crate::validation::DataDogValidation;
+#[cfg(feature = "recorder")]
+use crate::DataDogHandle;
+use crate::Error;
+
+/// Default total timeout of requests to the DataDog API
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default timeout of connecting to the DataDog API
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct DataDogConfig {
+    #[cfg(feature = "stdout")]
     pub write_to_stdout: bool,
     pub write_to_api: bool,
     pub api_host: String,
-    pub api_key: Option<String>,
+    pub fallback_api_hosts: Vec<String>,
+    pub api_key: Option<Arc<dyn DataDogApiKeyProvider>>,
     pub tags: Vec<Label>,
-    pub client_timeout: Option<Duration>,
     pub gzip: bool,
+    pub series_types: DataDogSeriesTypePolicy,
+    pub sender_shards: Option<usize>,
+    pub offload_blocking: bool,
+    pub app_key: Option<String>,
+    pub metadata_rate_limit: u32,
+    pub unit_tags: bool,
+    pub strict_intake: bool,
+    pub panic_on_intake_error: bool,
+    pub trace_headers: bool,
+    pub api_key_routing: Option<(String, HashMap<String, String>)>,
+    pub gauge_aggregation: Option<DataDogGaugeAggregation>,
+    pub clock_skew_tolerance: Option<Duration>,
+    pub namespace_tags: Vec<(String, Vec<Label>)>,
+    pub max_series: Option<usize>,
+    pub high_priority: Vec<String>,
+    pub latency_slo: Option<(DataDogLatencySlo, LatencyCallback)>,
+    pub tag_resumed_flush: bool,
+    pub high_resolution: bool,
+    pub metadata_overrides: HashMap<String, DataDogMetadata>,
+    pub keepalive: Option<Duration>,
+    pub idempotency_window: Option<Duration>,
+    pub compression_thread: bool,
+    pub tag_filters: Vec<DataDogTagFilter>,
+    pub empty_flush: DataDogEmptyFlush,
+    pub shadow: Option<(f64, ShadowCallback)>,
+    pub stage_timings: bool,
+    pub api_version: DataDogApiVersion,
+    pub distributions: bool,
+    pub write_to_dogstatsd: bool,
+    pub dogstatsd_addr: String,
+    pub dogstatsd_socket: Option<String>,
+    pub recent_flushes: Option<usize>,
+    pub host: Option<String>,
+    pub metadata_sync_interval: Option<Duration>,
+    pub retry: Option<DataDogRetryPolicy>,
+    pub shutdown_report: Option<DeliveryReportCallback>,
+    pub compression_budget: Option<Duration>,
+    pub flush_jitter: Option<DataDogJitter>,
+    pub counter_temporality: DataDogCounterTemporality,
+    pub sources: Vec<Arc<dyn DataDogMetricSource>>,
+    pub preserve_gauges: bool,
+    pub idle_timeout: Option<Duration>,
+    pub histogram_aggregates: Option<Vec<DataDogHistogramAggregate>>,
+    pub request_concurrency: Option<(usize, Vec<u32>)>,
+    pub histograms: HistogramStores,
+    pub request_signer: Option<Arc<dyn DataDogRequestSigner>>,
+    pub namespace: Option<String>,
+    pub memory_budget: Option<usize>,
+    pub runtime: Option<Handle>,
+    pub checkpoint_on_crash: bool,
+    pub flush_assertions: Option<ViolationCallback>,
+    pub gauge_bounds: HashMap<String, RangeInclusive<f64>>,
+    pub sinks: Vec<Arc<dyn DataDogSink>>,
+    pub destinations: Vec<ApiDestination>,
+    pub accepted_encodings: HashMap<String, Vec<DataDogContentEncoding>>,
 }
 
 /// Builder for creating/installing a DataDog recorder/exporter
@@ -24,28 +124,238 @@ pub struct DataDogBuilder {
     write_to_stdout: bool,
     write_to_api: bool,
     api_host: String,
+    fallback_api_hosts: Vec<String>,
     api_key: Option<String>,
+    api_key_provider: Option<Arc<dyn DataDogApiKeyProvider>>,
     tags: Vec<Label>,
     client_timeout: Option<Duration>,
     gzip: bool,
+    series_types: DataDogSeriesTypePolicy,
+    sender_shards: Option<usize>,
+    offload_blocking: bool,
+    app_key: Option<String>,
+    metadata_rate_limit: u32,
+    unit_tags: bool,
+    strict_intake: bool,
+    panic_on_intake_error: bool,
+    trace_headers: bool,
+    tag_context: HashMap<String, String>,
+    api_key_routing: Option<(String, HashMap<String, String>)>,
+    gauge_aggregation: Option<DataDogGaugeAggregation>,
+    clock_skew_tolerance: Option<Duration>,
+    namespace_tags: Vec<(String, Vec<Label>)>,
+    max_series: Option<usize>,
+    high_priority: Vec<String>,
+    latency_slo: Option<(DataDogLatencySlo, LatencyCallback)>,
+    #[cfg(feature = "recorder")]
+    validation: DataDogValidation,
+    tag_resumed_flush: bool,
+    high_resolution: bool,
+    metadata_overrides: HashMap<String, DataDogMetadata>,
+    keepalive: Option<Duration>,
+    idempotency_window: Option<Duration>,
+    compression_thread: bool,
+    tag_filters: Vec<DataDogTagFilter>,
+    empty_flush: DataDogEmptyFlush,
+    shadow: Option<(f64, ShadowCallback)>,
+    stage_timings: bool,
+    api_version: DataDogApiVersion,
+    distributions: bool,
+    write_to_dogstatsd: bool,
+    dogstatsd_addr: String,
+    dogstatsd_socket: Option<String>,
+    recent_flushes: Option<usize>,
+    host: Option<String>,
+    metadata_sync_interval: Option<Duration>,
+    retry: Option<DataDogRetryPolicy>,
+    shutdown_report: Option<DeliveryReportCallback>,
+    compression_budget: Option<Duration>,
+    flush_jitter: Option<DataDogJitter>,
+    counter_temporality: DataDogCounterTemporality,
+    sources: Vec<Arc<dyn DataDogMetricSource>>,
+    preserve_gauges: bool,
+    idle_timeout: Option<Duration>,
+    histogram_aggregates: Option<Vec<DataDogHistogramAggregate>>,
+    request_concurrency: Option<(usize, Vec<u32>)>,
+    #[cfg(feature = "recorder")]
+    histogram_sketches: Option<f64>,
+    request_signer: Option<Arc<dyn DataDogRequestSigner>>,
+    #[cfg(feature = "recorder")]
+    histogram_sample_limit: Option<usize>,
+    namespace: Option<String>,
+    memory_budget: Option<usize>,
+    runtime: Option<Handle>,
+    checkpoint_on_crash: bool,
+    proxy: Option<DataDogProxy>,
+    system_proxy: bool,
+    root_certificates: Vec<Vec<u8>>,
+    client_identity: Option<Vec<u8>>,
+    flush_assertions: Option<ViolationCallback>,
+    gauge_bounds: HashMap<String, RangeInclusive<f64>>,
+    connect_timeout: Option<Duration>,
+    sinks: Vec<Arc<dyn DataDogSink>>,
+    accepted_encodings: HashMap<String, Vec<DataDogContentEncoding>>,
+    destinations: Vec<DataDogDestination>,
 }
 
 impl Default for DataDogBuilder {
     fn default() -> Self {
         DataDogBuilder {
-            write_to_stdout: true,
+            write_to_stdout: cfg!(feature = "stdout"),
             write_to_api: false,
-            api_host: "https://api.datadoghq.com/api/v1".to_string(),
+            api_host: DataDogSite::default().api_host(),
+            fallback_api_hosts: vec![],
             api_key: None,
+            api_key_provider: None,
             tags: vec![],
-            client_timeout: None,
+            client_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
             gzip: true,
+            series_types: DataDogSeriesTypePolicy::default(),
+            sender_shards: None,
+            offload_blocking: false,
+            app_key: None,
+            metadata_rate_limit: 10,
+            unit_tags: false,
+            strict_intake: false,
+            panic_on_intake_error: false,
+            trace_headers: false,
+            tag_context: HashMap::new(),
+            api_key_routing: None,
+            gauge_aggregation: None,
+            clock_skew_tolerance: None,
+            namespace_tags: vec![],
+            max_series: None,
+            high_priority: vec![],
+            latency_slo: None,
+            #[cfg(feature = "recorder")]
+            validation: DataDogValidation::default(),
+            tag_resumed_flush: false,
+            high_resolution: false,
+            metadata_overrides: HashMap::new(),
+            keepalive: None,
+            idempotency_window: None,
+            compression_thread: false,
+            tag_filters: vec![],
+            empty_flush: DataDogEmptyFlush::default(),
+            shadow: None,
+            stage_timings: false,
+            api_version: DataDogApiVersion::default(),
+            distributions: false,
+            write_to_dogstatsd: false,
+            dogstatsd_addr: DEFAULT_ADDR.to_string(),
+            dogstatsd_socket: None,
+            recent_flushes: None,
+            host: None,
+            metadata_sync_interval: None,
+            retry: None,
+            shutdown_report: None,
+            compression_budget: None,
+            flush_jitter: None,
+            counter_temporality: DataDogCounterTemporality::Delta,
+            sources: vec![],
+            preserve_gauges: false,
+            idle_timeout: None,
+            histogram_aggregates: None,
+            request_concurrency: None,
+            #[cfg(feature = "recorder")]
+            histogram_sketches: None,
+            request_signer: None,
+            #[cfg(feature = "recorder")]
+            histogram_sample_limit: None,
+            namespace: None,
+            memory_budget: None,
+            runtime: None,
+            checkpoint_on_crash: false,
+            proxy: None,
+            system_proxy: true,
+            root_certificates: vec![],
+            client_identity: None,
+            flush_assertions: None,
+            gauge_bounds: HashMap::new(),
+            accepted_encodings: HashMap::new(),
+            destinations: vec![],
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            sinks: vec![],
         }
     }
 }
 
 impl DataDogBuilder {
-    /// Write metrics to stdout in DataDog JSON format
+    /// Builder configured from the environment variables used by the DataDog agent and SDKs
+    ///
+    /// - `DD_API_KEY` enables writing to the API with this key
+    /// - `DD_SITE`, e.g. `datadoghq.eu`, sets the API host to `https://api.{DD_SITE}/api/v1`
+    /// - `DD_TAGS` adds `key:value` tags separated by spaces or commas
+    /// - `DD_ENV`, `DD_SERVICE` and `DD_VERSION` add the unified service tags, replacing the same
+    ///   tags in `DD_TAGS`
+    ///
+    /// Unset or empty variables keep the defaults, and settings made after this override them.
+    /// Tags in `DD_TAGS` without a value, e.g. `foo` in `foo,team:payments`, are skipped with a
+    /// warning.
+    ///
+    /// ```
+    /// # use metrics_datadog_exporter::DataDogBuilder;
+    /// let builder = DataDogBuilder::from_env().write_to_stdout(false);
+    /// ```
+    pub fn from_env() -> DataDogBuilder {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let mut builder = DataDogBuilder::default();
+        if let Some(api_key) = var("DD_API_KEY") {
+            builder = builder.write_to_api(true, Some(api_key));
+        }
+        if let Some(site) = var("DD_SITE") {
+            builder = builder.api_host(format!("https://api.{}/api/v1", site.trim()));
+        }
+
+        let unified = [
+            ("env", var("DD_ENV")),
+            ("service", var("DD_SERVICE")),
+            ("version", var("DD_VERSION")),
+        ];
+        let mut tags = var("DD_TAGS")
+            .iter()
+            .flat_map(|tags| tags.split([' ', ',']))
+            .filter(|tag| !tag.is_empty())
+            .filter_map(|tag| {
+                let Some((key, value)) = tag.split_once(':').filter(|(_, value)| !value.is_empty())
+                else {
+                    warn!(tag, "Skipping DD_TAGS tag without a value");
+                    return None;
+                };
+                // Values are templates, keep braces literal
+                let value = value.replace('{', "{{").replace('}', "}}");
+                Some((key.to_string(), value))
+            })
+            .filter(|(key, _)| {
+                !unified
+                    .iter()
+                    .any(|(name, value)| name == key && value.is_some())
+            })
+            .collect::<Vec<_>>();
+        for (key, value) in unified {
+            if let Some(value) = value {
+                tags.push((key.to_string(), value.replace('{', "{{").replace('}', "}}")));
+            }
+        }
+        builder.add_tags(tags)
+    }
+
+    /// Apply the settings of `preset`, overridden by settings made after it
+    ///
+    /// ```
+    /// # use metrics_datadog_exporter::{DataDogBuilder, DataDogPreset};
+    /// let builder = DataDogBuilder::default()
+    ///     .preset(DataDogPreset::HighThroughput)
+    ///     .sender_shards(8);
+    /// ```
+    #[must_use]
+    pub fn preset(self, preset: DataDogPreset) -> DataDogBuilder {
+        preset.apply(self)
+    }
+
+    /// Write metrics to stdout in DataDog JSON format, enabled by default with the `stdout` feature
+    ///
+    /// Building fails with [`Error::StdoutDisabled`] if enabled without the `stdout` feature
     #[must_use]
     pub fn write_to_stdout(self, b: bool) -> DataDogBuilder {
         DataDogBuilder {
@@ -64,22 +374,130 @@ impl DataDogBuilder {
         }
     }
 
+    /// Resolve the API key with `provider` on every flush, taking precedence over the key passed
+    /// to [`DataDogBuilder::write_to_api`]
+    #[must_use]
+    pub fn api_key_provider(
+        self,
+        provider: impl DataDogApiKeyProvider + 'static,
+    ) -> DataDogBuilder {
+        DataDogBuilder {
+            api_key_provider: Some(Arc::new(provider)),
+            ..self
+        }
+    }
+
+    /// Pass every request to the DataDog API through `signer` before sending it, e.g. to sign it
+    /// for an egress gateway
+    #[must_use]
+    pub fn request_signer(self, signer: impl DataDogRequestSigner + 'static) -> DataDogBuilder {
+        DataDogBuilder {
+            request_signer: Some(Arc::new(signer)),
+            ..self
+        }
+    }
+
     /// Set DataDog API host
     #[must_use]
     pub fn api_host(self, api_host: String) -> DataDogBuilder {
         DataDogBuilder { api_host, ..self }
     }
 
+    /// Send to the API host of `site`, e.g. [`DataDogSite::EU1`] for `https://api.datadoghq.eu`
+    #[must_use]
+    pub fn site(self, site: DataDogSite) -> DataDogBuilder {
+        self.api_host(site.api_host())
+    }
+
+    /// Set DataDog API hosts to fail over to, in order, when the API host is unreachable
+    #[must_use]
+    pub fn fallback_api_hosts(self, fallback_api_hosts: Vec<String>) -> DataDogBuilder {
+        DataDogBuilder {
+            fallback_api_hosts,
+            ..self
+        }
+    }
+
+    /// Prefix the name of every recorded metric with `namespace`, e.g. `myapp` to export
+    /// `http.requests` as `myapp.http.requests`
+    ///
+    /// Names passed to the other settings, e.g. [`high_priority`](Self::high_priority) or
+    /// [`namespace_tags`](Self::namespace_tags), are the names before prefixing. Metrics passed to
+    /// [`DataDogExporter::export_metrics`] and the exporter's own metrics are not prefixed.
+    #[must_use]
+    pub fn namespace(self, namespace: String) -> DataDogBuilder {
+        DataDogBuilder {
+            namespace: Some(namespace),
+            ..self
+        }
+    }
+
     /// Set tags to send with metrics
+    ///
+    /// Building fails with [`Error::InvalidTag`] if a tag is malformed
+    #[must_use]
+    pub fn tags(self, tags: impl Into<DataDogTags>) -> DataDogBuilder {
+        DataDogBuilder {
+            tags: labels(tags),
+            ..self
+        }
+    }
+
+    /// Add tags to send with metrics, keeping the tags set so far
+    #[must_use]
+    pub fn add_tags(mut self, tags: impl Into<DataDogTags>) -> DataDogBuilder {
+        self.tags.extend(labels(tags));
+        self
+    }
+
+    /// Send requests to the DataDog API through `proxy`, ignoring the proxy environment variables
+    #[must_use]
+    pub fn proxy(self, proxy: DataDogProxy) -> DataDogBuilder {
+        DataDogBuilder {
+            proxy: Some(proxy),
+            ..self
+        }
+    }
+
+    /// Honor the `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables
+    /// when no [`proxy`](Self::proxy) is set, enabled by default
+    #[must_use]
+    pub fn system_proxy(self, system_proxy: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            system_proxy,
+            ..self
+        }
+    }
+
+    /// Trust the PEM encoded root certificate `pem` in addition to the system ones, e.g. the
+    /// private CA of a TLS inspecting gateway
+    ///
+    /// Building fails with [`Error::ApiError`] if the certificate can't be parsed, and with
+    /// [`Error::TlsDisabled`] without a TLS backend
+    #[must_use]
+    pub fn add_root_certificate(mut self, pem: Vec<u8>) -> DataDogBuilder {
+        self.root_certificates.push(pem);
+        self
+    }
+
+    /// Authenticate with the client certificate and private key in `pem`, for gateways
+    /// requiring mutual TLS
+    ///
+    /// The key must be PKCS#8 encoded with the `native-tls` backend. Building fails with
+    /// [`Error::ApiError`] if the identity can't be parsed, and with [`Error::TlsDisabled`]
+    /// without a TLS backend
     #[must_use]
-    pub fn tags(self, tags: Vec<(String, String)>) -> DataDogBuilder {
+    pub fn client_identity(self, pem: Vec<u8>) -> DataDogBuilder {
         DataDogBuilder {
-            tags: tags.iter().map(Label::from).collect(),
+            client_identity: Some(pem),
             ..self
         }
     }
 
-    /// Set client timeout
+    /// Set the total timeout of each request to the DataDog API, from connecting to reading the
+    /// response, 30 seconds by default
+    ///
+    /// A hung connection fails the request once the timeout elapses instead of stalling the flush
     pub fn client_timeout(self, timeout: Duration) -> DataDogBuilder {
         DataDogBuilder {
             client_timeout: Some(timeout),
@@ -87,15 +505,759 @@ impl DataDogBuilder {
         }
     }
 
+    /// Set the timeout of connecting to the DataDog API, 10 seconds by default
+    #[must_use]
+    pub fn connect_timeout(self, timeout: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            connect_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Also submit metrics to the API of another organization, e.g. while migrating to it
+    ///
+    /// Destinations have their own API key and hosts. Payloads are encoded, retried and failed
+    /// over as configured on this builder, independently of the other destinations: one
+    /// destination failing or being rate limited doesn't delay the others nor fail the flush,
+    /// it is reported in [`DataDogStatus::destinations`](crate::DataDogStatus::destinations)
+    /// instead.
+    #[must_use]
+    pub fn destination(mut self, destination: DataDogDestination) -> DataDogBuilder {
+        self.destinations.push(destination);
+        self
+    }
+
+    /// Only send payloads encoded with `encodings` to `host`, the API host or a fallback one
+    ///
+    /// Payloads are re-encoded for hosts that don't accept their encoding, and uncompressed
+    /// payloads are accepted by every host. Hosts without accepted encodings are assumed to accept
+    /// all of them until they reject one with 415 Unsupported Media Type. Zstd is only sent to the
+    /// v2 series intake, with the `zstd` feature.
+    #[must_use]
+    pub fn accepted_encodings(
+        mut self,
+        host: String,
+        encodings: Vec<DataDogContentEncoding>,
+    ) -> DataDogBuilder {
+        self.accepted_encodings.insert(host, encodings);
+        self
+    }
+
     /// Set compression
     pub fn gzip(self, gzip: bool) -> DataDogBuilder {
         DataDogBuilder { gzip, ..self }
     }
 
+    /// Set the series type submitted for each metric type
+    #[must_use]
+    pub fn series_types(self, series_types: DataDogSeriesTypePolicy) -> DataDogBuilder {
+        DataDogBuilder {
+            series_types,
+            ..self
+        }
+    }
+
+    /// Send at most `max_requests` requests at once, split between the API hosts by `weights`,
+    /// primary first, so a slow fallback host can't hold the requests meant for the primary
+    ///
+    /// Hosts without a weight get a weight of 1 and every host is allowed at least one request.
+    /// Limits and time spent waiting for them are reported in [`DataDogStatus::endpoints`].
+    ///
+    /// [`DataDogStatus::endpoints`]: crate::DataDogStatus::endpoints
+    #[must_use]
+    pub fn request_concurrency(self, max_requests: usize, weights: Vec<u32>) -> DataDogBuilder {
+        DataDogBuilder {
+            request_concurrency: Some((max_requests, weights)),
+            ..self
+        }
+    }
+
+    /// Shard series by name and tags across `shards` concurrent senders
+    ///
+    /// Each shard sends its requests in order, so points of a series are never reordered
+    #[must_use]
+    pub fn sender_shards(self, shards: usize) -> DataDogBuilder {
+        DataDogBuilder {
+            sender_shards: Some(shards.max(1)),
+            ..self
+        }
+    }
+
+    /// Run the flush task and blocking work on `runtime` instead of the current runtime, e.g. a
+    /// dedicated runtime keeping exports off the application's latency-critical one
+    ///
+    /// [`DataDogExporter::schedule`] can then be called outside of a runtime
+    #[must_use]
+    pub fn runtime(self, runtime: Handle) -> DataDogBuilder {
+        DataDogBuilder {
+            runtime: Some(runtime),
+            ..self
+        }
+    }
+
+    /// Write metrics as JSON lines to stderr when the process crashes, so the last flush isn't
+    /// lost when debugging it
+    ///
+    /// The metrics of the last flush are written on panic, before the previous panic hook runs,
+    /// and on `SIGABRT` on Unix. Recorded metrics are left for the next flush, so caught panics
+    /// don't lose them.
+    #[must_use]
+    pub fn checkpoint_on_crash(self, checkpoint_on_crash: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            checkpoint_on_crash,
+            ..self
+        }
+    }
+
+    /// Collect and serialize metrics on the blocking thread pool instead of the async executor
+    #[must_use]
+    pub fn offload_blocking(self, offload_blocking: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            offload_blocking,
+            ..self
+        }
+    }
+
+    /// Set DataDog application key, required by [`DataDogExporter::sync_metadata`]
+    #[must_use]
+    pub fn app_key(self, app_key: String) -> DataDogBuilder {
+        DataDogBuilder {
+            app_key: Some(app_key),
+            ..self
+        }
+    }
+
+    /// Set the maximum number of metadata API requests per second
+    #[must_use]
+    pub fn metadata_rate_limit(self, requests_per_second: u32) -> DataDogBuilder {
+        DataDogBuilder {
+            metadata_rate_limit: requests_per_second.max(1),
+            ..self
+        }
+    }
+
+    /// Push changed metric metadata every [`Duration`] while scheduled, requires
+    /// [`DataDogBuilder::app_key`]
+    ///
+    /// See [`DataDogExporter::sync_metadata`]. An empty interval is raised to 1 millisecond.
+    #[must_use]
+    pub fn metadata_sync_interval(self, interval: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            metadata_sync_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Tag series of described metrics with their unit, e.g. `unit:byte`
+    #[must_use]
+    pub fn unit_tags(self, unit_tags: bool) -> DataDogBuilder {
+        DataDogBuilder { unit_tags, ..self }
+    }
+
+    /// Fail the flush if DataDog does not accept a request with `202 Accepted` and no errors
+    #[must_use]
+    pub fn strict_intake(self, strict_intake: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            strict_intake,
+            ..self
+        }
+    }
+
+    /// Panic instead of failing the flush when strict intake checks fail, in debug builds only
+    #[must_use]
+    pub fn panic_on_intake_error(self, panic_on_intake_error: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            panic_on_intake_error,
+            ..self
+        }
+    }
+
+    /// Attach DataDog trace propagation headers to each API request, next to `x-request-id`
+    #[must_use]
+    pub fn trace_headers(self, trace_headers: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            trace_headers,
+            ..self
+        }
+    }
+
+    /// Provide a value for `{name}` placeholders in tag values
+    ///
+    /// Tag values may also refer to environment variables with `{env:NAME}`. Placeholders are
+    /// resolved once when building, and building fails if one cannot be resolved.
+    #[must_use]
+    pub fn tag_context(mut self, name: String, value: String) -> DataDogBuilder {
+        self.tag_context.insert(name, value);
+        self
+    }
+
+    /// Send series tagged `tag:<value>` with the API key mapped to `<value>` in `api_keys`
+    ///
+    /// Series without the tag, or with an unmapped value, are sent with the default API key
+    #[must_use]
+    pub fn route_api_keys(self, tag: String, api_keys: HashMap<String, String>) -> DataDogBuilder {
+        DataDogBuilder {
+            api_key_routing: Some((tag, api_keys)),
+            ..self
+        }
+    }
+
+    /// Combine the values of each gauge into a single point per flush
+    ///
+    /// By default every value collected for a gauge is submitted
+    #[must_use]
+    pub fn gauge_aggregation(self, gauge_aggregation: DataDogGaugeAggregation) -> DataDogBuilder {
+        DataDogBuilder {
+            gauge_aggregation: Some(gauge_aggregation),
+            ..self
+        }
+    }
+
+    /// Report counters as increments since the previous flush, the default, or as cumulative
+    /// totals
+    #[must_use]
+    pub fn counter_temporality(
+        self,
+        counter_temporality: DataDogCounterTemporality,
+    ) -> DataDogBuilder {
+        DataDogBuilder {
+            counter_temporality,
+            ..self
+        }
+    }
+
+    /// Submit the `aggregates` of each histogram's observations instead of every observation
+    ///
+    /// ```
+    /// # use metrics_datadog_exporter::{DataDogBuilder, DataDogHistogramAggregate};
+    /// let builder = DataDogBuilder::default().histogram_aggregates(vec![
+    ///     DataDogHistogramAggregate::Count,
+    ///     DataDogHistogramAggregate::Avg,
+    ///     DataDogHistogramAggregate::Percentile(99.0),
+    /// ]);
+    /// ```
+    #[must_use]
+    pub fn histogram_aggregates(
+        self,
+        histogram_aggregates: Vec<DataDogHistogramAggregate>,
+    ) -> DataDogBuilder {
+        DataDogBuilder {
+            histogram_aggregates: Some(histogram_aggregates),
+            ..self
+        }
+    }
+
+    /// Store the observations of each histogram in a DDSketch, within `relative_accuracy` of the
+    /// exact quantiles, e.g. `0.01`, instead of keeping every observation until the next flush
+    ///
+    /// Sketches are submitted as their [`histogram_aggregates`](Self::histogram_aggregates), by
+    /// default the count, average, maximum, median and 95th percentile like the DataDog agent.
+    /// Only histograms recorded by the recorder of [`build`](Self::build) are sketched.
+    #[cfg(feature = "recorder")]
+    #[must_use]
+    pub fn histogram_sketches(self, relative_accuracy: f64) -> DataDogBuilder {
+        DataDogBuilder {
+            histogram_sketches: Some(relative_accuracy),
+            ..self
+        }
+    }
+
+    /// Keep at most `limit` observations of each histogram between flushes, a uniform random
+    /// sample of them, so a hot path can't exhaust memory
+    ///
+    /// Discarded observations are counted in
+    /// [`DataDogStatus::discarded_samples`](crate::DataDogStatus::discarded_samples).
+    /// [`histogram_sketches`](Self::histogram_sketches) take precedence when both are set. Only
+    /// histograms recorded by the recorder of [`build`](Self::build) are sampled.
+    #[cfg(feature = "recorder")]
+    #[must_use]
+    pub fn histogram_sample_limit(self, limit: usize) -> DataDogBuilder {
+        DataDogBuilder {
+            histogram_sample_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Keep reporting the last value of each gauge on every flush until it is set again
+    ///
+    /// By default gauges are cleared with each flush, so a gauge set once is only reported once
+    #[must_use]
+    pub fn preserve_gauges(self, preserve_gauges: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            preserve_gauges,
+            ..self
+        }
+    }
+
+    /// Drop cumulative counters and preserved gauges left untouched for `idle_timeout`, so metrics
+    /// of e.g. departed tenants stop being reported
+    ///
+    /// Any update touches a metric, even one leaving its value unchanged. Only metrics kept across
+    /// flushes expire, the others are cleared with every flush, and metrics the application holds
+    /// a handle to don't expire. An expired metric is reported again once recorded again.
+    #[must_use]
+    pub fn idle_timeout(self, idle_timeout: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            idle_timeout: Some(idle_timeout),
+            ..self
+        }
+    }
+
+    /// Correct timestamps when the local clock is more than `tolerance` away from the DataDog API
+    /// clock, as reported by API responses
+    ///
+    /// Timestamps too far in the future for DataDog to accept are clamped
+    #[must_use]
+    pub fn clock_skew_tolerance(self, tolerance: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            clock_skew_tolerance: Some(tolerance),
+            ..self
+        }
+    }
+
+    /// Add default tags to every metric under `namespace`, e.g. `kafka` for `kafka.consumer.lag`
+    ///
+    /// Tags of nested namespaces override tags of the same name from enclosing namespaces, and
+    /// the metric's own labels override both
+    #[must_use]
+    pub fn namespace_tags(
+        mut self,
+        namespace: String,
+        tags: impl Into<DataDogTags>,
+    ) -> DataDogBuilder {
+        self.namespace_tags.push((namespace, labels(tags)));
+        self
+    }
+
+    /// Cap the output of each flush at an estimated `bytes`, a soft limit checked on every flush
+    ///
+    /// Covers the collected metrics, mostly histogram observations, and the payloads held back
+    /// by rate limits. Past 80% of the budget the oldest held back payloads are dropped, then the
+    /// latest series of the metrics not marked with [`high_priority`](Self::high_priority), the
+    /// rest keeping their order. The pressure is reported in
+    /// [`DataDogStatus::memory`](crate::DataDogStatus::memory) and as the
+    /// `datadog.exporter.memory.pressure` gauge.
+    ///
+    /// Metrics are only estimated once collected, so this doesn't bound the memory recorded
+    /// between flushes: bound histograms with
+    /// [`histogram_sample_limit`](Self::histogram_sample_limit) or
+    /// [`histogram_sketches`](Self::histogram_sketches) for that.
+    #[must_use]
+    pub fn memory_budget(self, bytes: usize) -> DataDogBuilder {
+        DataDogBuilder {
+            memory_budget: Some(bytes),
+            ..self
+        }
+    }
+
+    /// Submit at most `max_series` series per flush
+    ///
+    /// When over the limit, series of metrics marked with [`high_priority`](Self::high_priority)
+    /// are kept first and the rest are dropped. Drops are logged and counted in
+    /// [`DataDogStatus::dropped_series`](crate::DataDogStatus::dropped_series)
+    #[must_use]
+    pub fn max_series(self, max_series: usize) -> DataDogBuilder {
+        DataDogBuilder {
+            max_series: Some(max_series),
+            ..self
+        }
+    }
+
+    /// Mark metrics, or namespaces of metrics, as high priority
+    ///
+    /// High priority series are the last to be dropped when the series budget is exceeded. While
+    /// the DataDog API rate limits submissions, or payloads it rejected with a 429 are held back,
+    /// only high priority series are submitted and the others are dropped and counted in
+    /// [`DataDogStatus::dropped_series`](crate::DataDogStatus::dropped_series)
+    #[must_use]
+    pub fn high_priority(mut self, metrics: Vec<String>) -> DataDogBuilder {
+        self.high_priority.extend(metrics);
+        self
+    }
+
+    /// Call `callback` when the latency of DataDog API requests starts or stops breaching `slo`
+    #[must_use]
+    pub fn latency_slo<F>(self, slo: DataDogLatencySlo, callback: F) -> DataDogBuilder
+    where
+        F: Fn(&DataDogLatencyAlert) + Send + Sync + 'static,
+    {
+        DataDogBuilder {
+            latency_slo: Some((slo, Arc::new(callback))),
+            ..self
+        }
+    }
+
+    /// Validate metric names and labels against DataDog naming rules when they are registered
+    ///
+    /// Defaults to [`DataDogValidation::Warn`] with debug assertions and
+    /// [`DataDogValidation::Off`] otherwise
+    #[cfg(feature = "recorder")]
+    #[must_use]
+    pub fn validation(self, validation: DataDogValidation) -> DataDogBuilder {
+        DataDogBuilder { validation, ..self }
+    }
+
+    /// Tag metrics of the first scheduled flush after a suspend with `resumed:true`
+    ///
+    /// Scheduled flushes missed while suspended are always collapsed into a single flush
+    #[must_use]
+    pub fn tag_resumed_flush(self, tag_resumed_flush: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            tag_resumed_flush,
+            ..self
+        }
+    }
+
+    /// Submit high resolution metrics, flushed every second
+    ///
+    /// Every series is submitted with a 1 second `interval`, so rates and counts are per second.
+    /// Payloads under 16 KiB are sent uncompressed to save latency, and a flush started while
+    /// another is in flight is skipped instead of queued. Schedule with
+    /// [`DataDogExporter::schedule`] using a 1 second interval.
+    #[must_use]
+    pub fn high_resolution(self, high_resolution: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            high_resolution,
+            ..self
+        }
+    }
+
+    /// Set the metadata of metric `name`, taking precedence over metadata from `describe_*`
+    ///
+    /// Useful for metrics emitted by libraries that never describe them. Used for unit tags and
+    /// [`DataDogExporter::sync_metadata`]
+    #[must_use]
+    pub fn describe(mut self, name: String, metadata: DataDogMetadata) -> DataDogBuilder {
+        self.metadata_overrides.insert(name, metadata);
+        self
+    }
+
+    /// Probe the API when no request was made for `keepalive`, between scheduled flushes
+    ///
+    /// Keeps the pooled connection warm, e.g. through NATs, so flushes don't pay for a new TLS
+    /// handshake. Warm and cold flushes are counted in [`DataDogStatus`](crate::DataDogStatus).
+    /// An empty `keepalive` is raised to 1 millisecond.
+    #[must_use]
+    pub fn keepalive(self, keepalive: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            keepalive: Some(keepalive),
+            ..self
+        }
+    }
+
+    /// Attach an `Idempotency-Key` header to each payload, hashed from its content and `window`
+    ///
+    /// Payloads identical to one already accepted in the same window are not sent again, so
+    /// retries racing with slow responses don't count points twice
+    #[must_use]
+    pub fn idempotency_keys(self, window: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            idempotency_window: Some(window),
+            ..self
+        }
+    }
+
+    /// Serialize and compress payloads on a dedicated OS thread named `dd-exporter-compress`
+    ///
+    /// Makes exporter cost easy to spot in CPU profiles. Takes precedence over
+    /// [`offload_blocking`](Self::offload_blocking)
+    #[must_use]
+    pub fn compression_thread(self, compression_thread: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            compression_thread,
+            ..self
+        }
+    }
+
+    /// Only collect series accepted by `filter`, checked against metric labels, then namespace
+    /// and global tags
+    ///
+    /// Filters accumulate, a series is collected only if all of them accept it
+    #[must_use]
+    pub fn tag_filter(mut self, filter: DataDogTagFilter) -> DataDogBuilder {
+        self.tag_filters.push(filter);
+        self
+    }
+
+    /// Collect metrics from `source` on every flush, alongside the recorded ones
+    #[must_use]
+    pub fn metric_source(mut self, source: impl DataDogMetricSource + 'static) -> DataDogBuilder {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    /// Also write every flush to `sink`, alongside stdout, DogStatsD and the DataDog API
+    #[must_use]
+    pub fn sink(mut self, sink: impl DataDogSink + 'static) -> DataDogBuilder {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Set what flushes collecting no metrics do, skipped by default
+    #[must_use]
+    pub fn on_empty_flush(self, empty_flush: DataDogEmptyFlush) -> DataDogBuilder {
+        DataDogBuilder {
+            empty_flush,
+            ..self
+        }
+    }
+
+    /// Check invariants between consecutive flushes, calling `callback` with each violation, e.g.
+    /// to catch instrumentation bugs in staging before they corrupt dashboards
+    ///
+    /// Gauges must stay within the bounds set with [`gauge_bounds`](Self::gauge_bounds). With
+    /// cumulative [`counter_temporality`](Self::counter_temporality), counts returned by
+    /// [`metric_source`](Self::metric_source)s are running totals and must not decrease. Recorded
+    /// counters can't decrease, nor be negative.
+    #[must_use]
+    pub fn flush_assertions<F>(self, callback: F) -> DataDogBuilder
+    where
+        F: Fn(&DataDogFlushViolation) + Send + Sync + 'static,
+    {
+        DataDogBuilder {
+            flush_assertions: Some(Arc::new(callback)),
+            ..self
+        }
+    }
+
+    /// Report gauges named `metric` outside of `bounds` to the
+    /// [`flush_assertions`](Self::flush_assertions) callback
+    #[must_use]
+    pub fn gauge_bounds(mut self, metric: String, bounds: RangeInclusive<f64>) -> DataDogBuilder {
+        self.gauge_bounds.insert(metric, bounds);
+        self
+    }
+
+    /// Also hand `percent` of flushes to `callback`, e.g. to compare them with another exporter
+    /// before migrating
+    ///
+    /// Shadowed flushes are still written to the API and stdout
+    #[must_use]
+    pub fn shadow<F>(self, percent: f64, callback: F) -> DataDogBuilder
+    where
+        F: Fn(&[DataDogMetric]) + Send + Sync + 'static,
+    {
+        DataDogBuilder {
+            shadow: Some((percent, Arc::new(callback))),
+            ..self
+        }
+    }
+
+    /// Record the duration of each flush stage as `datadog.exporter.flush.<stage>` histograms,
+    /// in seconds, exported with the next flush
+    ///
+    /// Durations of the last flush are always available in
+    /// [`DataDogStatus::last_flush`](crate::DataDogStatus::last_flush)
+    #[must_use]
+    pub fn stage_timings(self, stage_timings: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            stage_timings,
+            ..self
+        }
+    }
+
+    /// Set the version of the metrics API series are submitted to, v1 by default
+    ///
+    /// With [`DataDogApiVersion::V2`], series are posted to `/api/v2/series` on API hosts ending
+    /// with `/v1` like the default one, and to `{host}/series` on other hosts
+    #[must_use]
+    pub fn api_version(self, api_version: DataDogApiVersion) -> DataDogBuilder {
+        DataDogBuilder {
+            api_version,
+            ..self
+        }
+    }
+
+    /// Submit histogram observations to the distribution points intake as DataDog
+    /// distributions, with server-side percentiles, instead of as series
+    #[must_use]
+    pub fn distributions(self, distributions: bool) -> DataDogBuilder {
+        DataDogBuilder {
+            distributions,
+            ..self
+        }
+    }
+
+    /// Write metrics in the DogStatsD protocol over UDP to a local agent, at `127.0.0.1:8125`
+    /// unless `addr` is set
+    ///
+    /// The agent holds the API key and aggregates metrics, so no API key is needed here
+    #[must_use]
+    pub fn write_to_dogstatsd(self, b: bool, addr: Option<String>) -> DataDogBuilder {
+        DataDogBuilder {
+            write_to_dogstatsd: b,
+            dogstatsd_addr: addr.unwrap_or(self.dogstatsd_addr),
+            ..self
+        }
+    }
+
+    /// Write metrics in the DogStatsD protocol to the agent's Unix domain socket, at
+    /// `/var/run/datadog/dsd.socket` unless `path` is set, instead of over UDP
+    ///
+    /// The socket is connected on the first flush and reconnected after a failed send, e.g. when
+    /// the agent restarted
+    #[cfg(unix)]
+    #[must_use]
+    pub fn write_to_dogstatsd_socket(self, b: bool, path: Option<String>) -> DataDogBuilder {
+        DataDogBuilder {
+            write_to_dogstatsd: b,
+            dogstatsd_socket: Some(path.unwrap_or_else(|| DEFAULT_SOCKET.to_string())),
+            ..self
+        }
+    }
+
+    /// Keep the metrics written by the last `flushes` flushes, returned by
+    /// [`DataDogExporter::recent_flushes`]
+    #[must_use]
+    pub fn recent_flushes(self, flushes: usize) -> DataDogBuilder {
+        DataDogBuilder {
+            recent_flushes: Some(flushes),
+            ..self
+        }
+    }
+
+    /// Report every series for `host`, sent once per series as its host attribute, or host
+    /// resource with the v2 API, rather than as a `host` tag among the series tags
+    #[must_use]
+    pub fn host(self, host: String) -> DataDogBuilder {
+        DataDogBuilder {
+            host: Some(host),
+            ..self
+        }
+    }
+
+    /// Retry requests failing with a transient error following `policy`
+    #[must_use]
+    pub fn retry(self, policy: DataDogRetryPolicy) -> DataDogBuilder {
+        DataDogBuilder {
+            retry: Some(policy),
+            ..self
+        }
+    }
+
+    /// Call `callback` with the delivery totals on [`DataDogExporter::shutdown`], instead of
+    /// writing them to stdout as a line of JSON
+    #[must_use]
+    pub fn shutdown_report<F>(self, callback: F) -> DataDogBuilder
+    where
+        F: Fn(&DataDogDeliveryReport) + Send + Sync + 'static,
+    {
+        DataDogBuilder {
+            shutdown_report: Some(Arc::new(callback)),
+            ..self
+        }
+    }
+
+    /// Pick the compression of each payload from its size: none for tiny payloads, fast gzip for
+    /// medium ones and the best codec for huge ones, zstd with [`DataDogApiVersion::V2`] and the
+    /// `zstd` feature
+    ///
+    /// Every payload uses fast gzip after a flush spending more than `cpu_budget` encoding.
+    /// The codecs picked are counted in [`DataDogFlushStats`](crate::DataDogFlushStats).
+    #[must_use]
+    pub fn adaptive_compression(self, cpu_budget: Duration) -> DataDogBuilder {
+        DataDogBuilder {
+            compression_budget: Some(cpu_budget),
+            ..self
+        }
+    }
+
+    /// Move each scheduled flush by a random offset within `jitter`, so instances started together
+    /// don't all hit the DataDog intake at once
+    #[must_use]
+    pub fn flush_jitter(self, jitter: DataDogJitter) -> DataDogBuilder {
+        DataDogBuilder {
+            flush_jitter: Some(jitter),
+            ..self
+        }
+    }
+
     /// Build [`DataDogHandle`]
+    ///
+    /// Fails with [`Error::MissingApiKey`] if writing to the API without an API key
+    #[cfg(feature = "recorder")]
     pub fn build(self) -> Result<DataDogHandle, Error> {
-        let registry = Arc::new(Registry::new(AtomicStorage));
-        let recorder = DataDogRecorder::new(registry.clone());
+        let registry = Arc::new(Registry::new(GenerationalAtomicStorage::atomic()));
+        let metadata = MetadataStore::default();
+        let histograms = HistogramStores::new(self.histogram_sketches, self.histogram_sample_limit);
+        let recorder = DataDogRecorder::new(registry.clone(), metadata.clone(), self.validation)
+            .with_histograms(histograms.clone());
+        let handle = self.build_with(registry, metadata, histograms)?;
+        Ok(DataDogHandle { recorder, handle })
+    }
+
+    /// Build [`DataDogExporter`] without a recorder, to export metrics recorded elsewhere with
+    /// [`DataDogExporter::export_metrics`]
+    ///
+    /// Fails with [`Error::MissingApiKey`] if writing to the API without an API key
+    pub fn build_exporter(self) -> Result<DataDogExporter, Error> {
+        self.build_with(
+            Arc::new(Registry::new(GenerationalAtomicStorage::atomic())),
+            MetadataStore::default(),
+            HistogramStores::default(),
+        )
+    }
+
+    /// Builder of the exporter submitting to `destination`, with the request and encoding
+    /// settings of this builder
+    fn destination_builder(&self, destination: &DataDogDestination) -> DataDogBuilder {
+        DataDogBuilder {
+            write_to_stdout: false,
+            write_to_api: true,
+            api_host: destination.api_host.clone(),
+            fallback_api_hosts: destination.fallback_api_hosts.clone(),
+            api_key: Some(destination.api_key.clone()),
+            client_timeout: self.client_timeout,
+            connect_timeout: self.connect_timeout,
+            gzip: self.gzip,
+            series_types: self.series_types,
+            sender_shards: self.sender_shards,
+            offload_blocking: self.offload_blocking,
+            strict_intake: self.strict_intake,
+            panic_on_intake_error: self.panic_on_intake_error,
+            trace_headers: self.trace_headers,
+            high_resolution: self.high_resolution,
+            idempotency_window: self.idempotency_window,
+            api_version: self.api_version,
+            distributions: self.distributions,
+            host: self.host.clone(),
+            retry: self.retry,
+            compression_budget: self.compression_budget,
+            request_concurrency: self.request_concurrency.clone(),
+            request_signer: self.request_signer.clone(),
+            runtime: self.runtime.clone(),
+            proxy: self.proxy.clone(),
+            system_proxy: self.system_proxy,
+            root_certificates: self.root_certificates.clone(),
+            client_identity: self.client_identity.clone(),
+            accepted_encodings: self.accepted_encodings.clone(),
+            ..DataDogBuilder::default()
+        }
+    }
+
+    fn build_with(
+        self,
+        registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+        metadata: MetadataStore,
+        histograms: HistogramStores,
+    ) -> Result<DataDogExporter, Error> {
+        if self.write_to_api && self.api_key.is_none() && self.api_key_provider.is_none() {
+            return Err(Error::MissingApiKey);
+        }
+        if self.write_to_stdout && !cfg!(feature = "stdout") {
+            return Err(Error::StdoutDisabled);
+        }
+
+        let tags = resolve_labels(&self.tags, &self.tag_context)?;
+        let mut namespace_tags = self
+            .namespace_tags
+            .iter()
+            .map(|(namespace, tags)| {
+                Ok((namespace.clone(), resolve_labels(tags, &self.tag_context)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // Enclosing namespaces first, so nested namespaces override them
+        namespace_tags.sort_by_key(|(namespace, _)| namespace.split('.').count());
 
         let client = if self.write_to_api {
             let mut c = Client::builder();
@@ -103,22 +1265,151 @@ impl DataDogBuilder {
             if let Some(timeout) = self.client_timeout {
                 c = c.timeout(timeout);
             }
+            if let Some(timeout) = self.connect_timeout {
+                c = c.connect_timeout(timeout);
+            }
+            #[cfg(feature = "rustls")]
+            {
+                c = c.use_rustls_tls();
+            }
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            {
+                for pem in &self.root_certificates {
+                    c = c.add_root_certificate(Certificate::from_pem(pem)?);
+                }
+                if let Some(pem) = &self.client_identity {
+                    c = c.identity(client_identity(pem)?);
+                }
+            }
+            #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+            if !self.root_certificates.is_empty() || self.client_identity.is_some() {
+                return Err(Error::TlsDisabled);
+            }
+            if let Some(proxy) = &self.proxy {
+                c = c.proxy(proxy.to_reqwest()?);
+            } else if !self.system_proxy {
+                c = c.no_proxy();
+            }
 
             Some(c.build()?)
         } else {
             None
         };
 
+        let destinations = self
+            .destinations
+            .iter()
+            .map(|destination| {
+                let exporter = self.destination_builder(destination).build_exporter()?;
+                Ok(ApiDestination::new(destination.api_host.clone(), exporter))
+            })
+            .collect::<Result<_, Error>>()?;
+
         let config = DataDogConfig {
+            #[cfg(feature = "stdout")]
             write_to_stdout: self.write_to_stdout,
             write_to_api: self.write_to_api,
             api_host: self.api_host,
-            api_key: self.api_key,
-            tags: self.tags,
-            client_timeout: self.client_timeout,
+            fallback_api_hosts: self.fallback_api_hosts,
+            api_key: self.api_key_provider.or_else(|| {
+                self.api_key
+                    .map(|api_key| Arc::new(api_key) as Arc<dyn DataDogApiKeyProvider>)
+            }),
+            tags,
             gzip: self.gzip,
+            series_types: self.series_types,
+            sender_shards: self.sender_shards,
+            offload_blocking: self.offload_blocking,
+            app_key: self.app_key,
+            metadata_rate_limit: self.metadata_rate_limit,
+            unit_tags: self.unit_tags,
+            strict_intake: self.strict_intake,
+            panic_on_intake_error: self.panic_on_intake_error,
+            trace_headers: self.trace_headers,
+            api_key_routing: self.api_key_routing,
+            gauge_aggregation: self.gauge_aggregation,
+            clock_skew_tolerance: self.clock_skew_tolerance,
+            namespace_tags,
+            max_series: self.max_series,
+            high_priority: match &self.namespace {
+                Some(namespace) => self
+                    .high_priority
+                    .iter()
+                    .map(|name| format!("{namespace}.{name}"))
+                    .collect(),
+                None => self.high_priority,
+            },
+            latency_slo: self.latency_slo,
+            tag_resumed_flush: self.tag_resumed_flush,
+            high_resolution: self.high_resolution,
+            metadata_overrides: self.metadata_overrides,
+            keepalive: self.keepalive,
+            idempotency_window: self.idempotency_window,
+            compression_thread: self.compression_thread,
+            tag_filters: self.tag_filters,
+            empty_flush: self.empty_flush,
+            shadow: self.shadow,
+            stage_timings: self.stage_timings,
+            api_version: self.api_version,
+            distributions: self.distributions,
+            write_to_dogstatsd: self.write_to_dogstatsd,
+            dogstatsd_addr: self.dogstatsd_addr,
+            dogstatsd_socket: self.dogstatsd_socket,
+            recent_flushes: self.recent_flushes,
+            host: self.host,
+            metadata_sync_interval: self.metadata_sync_interval,
+            retry: self.retry,
+            shutdown_report: self.shutdown_report,
+            compression_budget: self.compression_budget,
+            flush_jitter: self.flush_jitter,
+            counter_temporality: self.counter_temporality,
+            sources: self.sources,
+            preserve_gauges: self.preserve_gauges,
+            idle_timeout: self.idle_timeout,
+            histogram_aggregates: self.histogram_aggregates,
+            request_concurrency: self.request_concurrency,
+            histograms,
+            request_signer: self.request_signer,
+            namespace: self.namespace,
+            memory_budget: self.memory_budget,
+            runtime: self.runtime,
+            checkpoint_on_crash: self.checkpoint_on_crash,
+            flush_assertions: self.flush_assertions,
+            gauge_bounds: self.gauge_bounds,
+            accepted_encodings: self.accepted_encodings,
+            sinks: self.sinks,
+            destinations,
         };
-        let handle = DataDogExporter::new(registry, client, config);
-        Ok(DataDogHandle { recorder, handle })
+        DataDogExporter::new(registry, metadata, client, config)
     }
 }
+
+#[cfg(feature = "rustls")]
+fn client_identity(pem: &[u8]) -> reqwest::Result<Identity> {
+    Identity::from_pem(pem)
+}
+
+/// native-tls reads the certificate chain and the PKCS#8 key from separate buffers, each skipping
+/// the other's blocks
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+fn client_identity(pem: &[u8]) -> reqwest::Result<Identity> {
+    Identity::from_pkcs8_pem(pem, pem)
+}
+
+fn labels(tags: impl Into<DataDogTags>) -> Vec<Label> {
+    tags.into().into_pairs().iter().map(Label::from).collect()
+}
+
+fn resolve_labels(
+    labels: &[Label],
+    context: &HashMap<String, String>,
+) -> Result<Vec<Label>, Error> {
+    labels
+        .iter()
+        .map(|label| {
+            let value = template::resolve(label.value(), context)?;
+            tags::validate(label.key(), &value)?;
+            Ok(Label::new(label.key().to_string(), value))
+        })
+        .collect()
+}
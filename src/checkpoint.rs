@@ -0,0 +1,120 @@
+//! Metrics checkpoints written to stderr when the process panics or aborts
+//!
+//! The metrics of each flush are rendered in advance, so crash handlers only write bytes: they
+//! don't collect, which would drain the registry on caught panics, nor take locks the crashing
+//! thread may hold.
+
+use std::io::Write;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Weak;
+
+use parking_lot::Mutex;
+
+use crate::collector::Collector;
+use crate::data::DataDogMetric;
+
+/// JSON lines of the metrics of the last flush
+struct Checkpoint {
+    lines: Vec<u8>,
+}
+
+static CHECKPOINT: AtomicPtr<Checkpoint> = AtomicPtr::new(null_mut());
+/// Checkpoint replaced by the last update, freed by the next one so a handler still writing it
+/// on another thread doesn't read freed memory
+static RETIRED: Mutex<Option<Box<Checkpoint>>> = Mutex::new(None);
+/// Checkpoint already written by the panic hook, not written again if the panic aborts
+static WRITTEN: AtomicPtr<Checkpoint> = AtomicPtr::new(null_mut());
+
+/// JSON lines of `metrics`, as written to stdout
+fn render(metrics: &[DataDogMetric]) -> Vec<u8> {
+    let mut lines = vec![];
+    for line in metrics.iter().flat_map(DataDogMetric::to_metric_lines) {
+        if serde_json::to_writer(&mut lines, &line).is_ok() {
+            lines.push(b'\n');
+        }
+    }
+    lines
+}
+
+/// Write the metrics of the last flush to stderr on panic and, on Unix, on `SIGABRT`
+///
+/// The panic hook wraps the current one and stops checkpointing once the exporter is dropped.
+pub(crate) fn install(collector: Weak<Collector>) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if collector.strong_count() > 0 {
+            let checkpoint = CHECKPOINT.load(Ordering::Acquire);
+            // SAFETY: checkpoints live until replaced twice
+            if let Some(checkpoint) = unsafe { checkpoint.as_ref() } {
+                std::io::stderr().lock().write_all(&checkpoint.lines).ok();
+            }
+            WRITTEN.store(checkpoint, Ordering::Release);
+        }
+        previous(info)
+    }));
+    #[cfg(unix)]
+    abort::install();
+}
+
+/// Keep the metrics of a flush for the crash handlers
+pub(crate) fn update(metrics: &[DataDogMetric]) {
+    let checkpoint = Box::new(Checkpoint {
+        lines: render(metrics),
+    });
+    let replaced = CHECKPOINT.swap(Box::into_raw(checkpoint), Ordering::AcqRel);
+    WRITTEN.store(null_mut(), Ordering::Release);
+    if !replaced.is_null() {
+        // SAFETY: checkpoints are created by `Box::into_raw` above and swapped out once
+        let replaced = unsafe { Box::from_raw(replaced) };
+        RETIRED.lock().replace(replaced);
+    }
+}
+
+/// The `SIGABRT` handler can't allocate or lock, so it writes the checkpoint as is
+#[cfg(unix)]
+mod abort {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Once;
+
+    use super::{CHECKPOINT, WRITTEN};
+
+    static PREVIOUS_HANDLER: AtomicUsize = AtomicUsize::new(libc::SIG_DFL);
+
+    pub(super) fn install() {
+        static INSTALLED: Once = Once::new();
+        INSTALLED.call_once(|| {
+            let handler = on_abort as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // SAFETY: the handler only calls async-signal-safe functions
+            let previous = unsafe { libc::signal(libc::SIGABRT, handler) };
+            if previous != libc::SIG_ERR {
+                PREVIOUS_HANDLER.store(previous, Ordering::Relaxed);
+            }
+        });
+    }
+
+    extern "C" fn on_abort(signal: libc::c_int) {
+        let checkpoint = CHECKPOINT.load(Ordering::Acquire);
+        if checkpoint == WRITTEN.load(Ordering::Acquire) {
+            // Written by the panic hook, or empty
+            return reraise(signal);
+        }
+        // SAFETY: checkpoints live until replaced twice and `write` is async-signal-safe
+        unsafe {
+            if let Some(checkpoint) = checkpoint.as_ref() {
+                let lines = &checkpoint.lines;
+                libc::write(libc::STDERR_FILENO, lines.as_ptr().cast(), lines.len());
+            }
+        }
+        reraise(signal);
+    }
+
+    fn reraise(signal: libc::c_int) {
+        // SAFETY: `signal` and `raise` are async-signal-safe, the previous handler is restored
+        // before raising again
+        unsafe {
+            libc::signal(signal, PREVIOUS_HANDLER.load(Ordering::Relaxed));
+            libc::raise(signal);
+        }
+    }
+}
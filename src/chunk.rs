@@ -0,0 +1,230 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::data::{DataDogApiVersion, DataDogSeries};
+use crate::exporter::{
+    split_and_compress_series, split_series, MAX_DECOMPRESSED_PAYLOAD, MAX_PAYLOAD_BYTES,
+};
+use crate::json;
+use crate::Result;
+
+const PREFIX: &[u8] = b"{\"series\":[";
+const SEPARATOR: &[u8] = b",";
+const SUFFIX: &[u8] = b"]}";
+// Headroom for the gzip trailer and deflate block overhead
+const MARGIN: usize = 16 * 1024;
+// Adaptive compression sends chunks below this uncompressed, compression wouldn't pay off
+const TINY_CHUNK: usize = 1024;
+// Adaptive compression uses the best codec for chunks above this, cheap gzip below
+const HUGE_CHUNK: usize = 1024 * 1024;
+
+/// Codec compressing a chunk of series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Codec {
+    /// Gzip at the given level
+    Gzip(u32),
+    /// Zstandard, only accepted by the v2 series intake
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Picks the codec of each chunk
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CodecSelector {
+    /// Pick the codec from the chunk size, otherwise always use gzip at the default level
+    pub(crate) adaptive: bool,
+    /// The previous flush spent more than its CPU budget encoding, use the cheapest codec
+    pub(crate) over_budget: bool,
+    /// Zstandard is accepted by the intake
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd: bool,
+}
+
+impl CodecSelector {
+    /// Codec for a chunk of about `bytes` uncompressed bytes, [`None`] to send it uncompressed
+    fn codec(&self, bytes: usize) -> Option<Codec> {
+        if !self.adaptive {
+            return Some(Codec::Gzip(Compression::default().level()));
+        }
+        if bytes < TINY_CHUNK {
+            return None;
+        }
+        if self.over_budget || bytes < HUGE_CHUNK {
+            return Some(Codec::Gzip(Compression::fast().level()));
+        }
+        #[cfg(feature = "zstd")]
+        if self.zstd {
+            return Some(Codec::Zstd);
+        }
+        Some(Codec::Gzip(Compression::default().level()))
+    }
+}
+
+/// Stream series into compressed payloads, starting a new payload whenever the next series
+/// might push the current one over the size limits
+///
+/// Each series is serialized exactly once. Only a payload that still ends up too large, which can
+/// only happen to a payload holding a single series, falls back to splitting. The codec of each
+/// payload is picked by `selector` from the size of the series left to encode.
+pub(crate) fn compress_series(
+    series: &[DataDogSeries],
+    version: DataDogApiVersion,
+    selector: CodecSelector,
+) -> Result<Vec<Vec<u8>>> {
+    let bodies = series
+        .iter()
+        .map(|s| json::series(s, version))
+        .collect::<Result<Vec<_>>>()?;
+    let mut remaining: usize = bodies.iter().map(Vec::len).sum();
+    let mut payloads = Vec::new();
+    let Some(codec) = selector.codec(remaining) else {
+        return split_series(series, version);
+    };
+    let mut chunk = Chunk::new(codec)?;
+    let mut start = 0;
+
+    for (index, body) in bodies.iter().enumerate() {
+        if index > start && !chunk.fits(body.len())? {
+            payloads.extend(chunk.finish(&series[start..index], version)?);
+            let Some(codec) = selector.codec(remaining) else {
+                payloads.extend(split_series(&series[index..], version)?);
+                return Ok(payloads);
+            };
+            chunk = Chunk::new(codec)?;
+            start = index;
+        }
+        chunk.write(body)?;
+        remaining -= body.len();
+    }
+    payloads.extend(chunk.finish(&series[start..], version)?);
+
+    Ok(payloads)
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(codec: Codec) -> Result<Self> {
+        Ok(match codec {
+            Codec::Gzip(level) => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::new(level)))
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(
+                Vec::new(),
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?),
+        })
+    }
+
+    /// Compressed bytes written so far
+    fn written(&self) -> usize {
+        match self {
+            Encoder::Gzip(encoder) => encoder.get_ref().len(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.get_ref().len(),
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        Ok(match self {
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+struct Chunk {
+    encoder: Encoder,
+    series: usize,
+    uncompressed: usize,
+    // Uncompressed bytes written since the encoder was last flushed
+    pending: usize,
+}
+
+impl Chunk {
+    fn new(codec: Codec) -> Result<Self> {
+        let mut encoder = Encoder::new(codec)?;
+        encoder.write_all(PREFIX)?;
+        Ok(Chunk {
+            encoder,
+            series: 0,
+            uncompressed: PREFIX.len(),
+            pending: PREFIX.len(),
+        })
+    }
+
+    /// Whether a series of `len` bytes is guaranteed to fit, assuming it doesn't compress at all
+    fn fits(&mut self, len: usize) -> Result<bool> {
+        let added = SEPARATOR.len() + len + SUFFIX.len();
+        if self.uncompressed + added > MAX_DECOMPRESSED_PAYLOAD {
+            return Ok(false);
+        }
+        if self.compressed_estimate() + added < MAX_PAYLOAD_BYTES {
+            return Ok(true);
+        }
+        // The estimate counts everything since the last flush as incompressible, so flush to
+        // measure the actual compressed size before giving up on this chunk
+        self.encoder.flush()?;
+        self.pending = 0;
+        Ok(self.compressed_estimate() + added < MAX_PAYLOAD_BYTES)
+    }
+
+    fn compressed_estimate(&self) -> usize {
+        self.encoder.written() + self.pending + MARGIN
+    }
+
+    fn write(&mut self, body: &[u8]) -> Result<()> {
+        if self.series > 0 {
+            self.encoder.write_all(SEPARATOR)?;
+            self.uncompressed += SEPARATOR.len();
+            self.pending += SEPARATOR.len();
+        }
+        self.encoder.write_all(body)?;
+        self.uncompressed += body.len();
+        self.pending += body.len();
+        self.series += 1;
+        Ok(())
+    }
+
+    /// Finish the payload holding `series`, splitting them up again if it is too large
+    fn finish(
+        mut self,
+        series: &[DataDogSeries],
+        version: DataDogApiVersion,
+    ) -> Result<Vec<Vec<u8>>> {
+        self.encoder.write_all(SUFFIX)?;
+        let uncompressed = self.uncompressed + SUFFIX.len();
+        let compressed = self.encoder.finish()?;
+        if compressed.len() < MAX_PAYLOAD_BYTES && uncompressed <= MAX_DECOMPRESSED_PAYLOAD {
+            Ok(vec![compressed])
+        } else {
+            split_and_compress_series(series, version)
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, DATE};
+use tracing::warn;
+
+use crate::data::DataDogMetric;
+
+/// DataDog rejects points more than 10 minutes in the future
+pub(crate) const MAX_FUTURE_SECS: i64 = 600;
+
+/// Corrects metric timestamps for a local clock that drifted from the DataDog API clock
+///
+/// The offset is learnt from the `Date` header of API responses
+pub(crate) struct ClockSkew {
+    tolerance: i64,
+    offset: AtomicI64,
+    warned: AtomicBool,
+}
+
+impl ClockSkew {
+    pub(crate) fn new(tolerance: Duration) -> Self {
+        ClockSkew {
+            tolerance: tolerance.as_secs() as i64,
+            offset: AtomicI64::new(0),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Update the offset from the `Date` header of a response
+    pub(crate) fn observe(&self, headers: &HeaderMap) {
+        let Some(date) = headers
+            .get(DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        else {
+            return;
+        };
+        let offset = date.timestamp() - Utc::now().timestamp();
+        self.offset.store(offset, Ordering::Relaxed);
+
+        let skewed = offset.abs() > self.tolerance;
+        if skewed && !self.warned.swap(true, Ordering::Relaxed) {
+            warn!(
+                offset_secs = offset,
+                "Local clock is skewed from the DataDog API clock, correcting timestamps"
+            );
+        } else if !skewed {
+            self.warned.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Shift timestamps by the offset when it exceeds the tolerance, and clamp timestamps
+    /// DataDog would reject as too far in the future
+    pub(crate) fn correct(&self, metrics: &mut [DataDogMetric]) {
+        let offset = self.offset.load(Ordering::Relaxed);
+        let offset = if offset.abs() > self.tolerance {
+            offset
+        } else {
+            0
+        };
+        let now = Utc::now().timestamp() + offset;
+
+        for metric in metrics {
+            let timestamp = metric.timestamp + offset;
+            if timestamp > now + MAX_FUTURE_SECS {
+                warn!(
+                    metric = %metric.metric,
+                    timestamp, "Clamping metric timestamp too far in the future"
+                );
+                metric.timestamp = now;
+            } else {
+                metric.timestamp = timestamp;
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::Utc;
+use itertools::Itertools;
+use metrics::{GaugeFn, HistogramFn, Key, Label};
+use metrics_util::registry::{GenerationalAtomicStorage, Registry};
+use parking_lot::Mutex;
+
+use crate::assertions::FlushAssertions;
+use crate::builder::DataDogConfig;
+use crate::data::{
+    DataDogCounterTemporality, DataDogGaugeAggregation, DataDogHistogramAggregate, DataDogMetric,
+    DataDogMetricType, DataDogMetricValue, DataDogTagFilter,
+};
+use crate::empty::{DataDogRegistryStats, HEARTBEAT_METRIC};
+use crate::histograms::HistogramStores;
+use crate::idle::IdleExpiry;
+use crate::metadata::{DataDogMetadata, MetadataStore};
+use crate::source::DataDogMetricSource;
+
+/// Namespace of the metrics recorded by the exporter about itself
+const SELF_METRICS: &str = "datadog.exporter.";
+
+/// Aggregates of histogram sketches submitted by default, as by the DataDog agent
+#[cfg(feature = "recorder")]
+const SKETCH_AGGREGATES: [DataDogHistogramAggregate; 5] = [
+    DataDogHistogramAggregate::Count,
+    DataDogHistogramAggregate::Avg,
+    DataDogHistogramAggregate::Max,
+    DataDogHistogramAggregate::Percentile(50.0),
+    DataDogHistogramAggregate::Percentile(95.0),
+];
+
+/// Keys and formatted tags by key hash, keyed by hash as keys hold atomics
+type TagCache = HashMap<u64, (Key, Vec<String>)>;
+
+/// Turns the registry contents into [`DataDogMetric`]s
+pub(crate) struct Collector {
+    registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+    metadata: MetadataStore,
+    metadata_overrides: HashMap<String, DataDogMetadata>,
+    tags: Vec<Label>,
+    namespace_tags: Vec<(String, Vec<Label>)>,
+    unit_tags: bool,
+    tag_filters: Vec<DataDogTagFilter>,
+    gauge_aggregation: Option<DataDogGaugeAggregation>,
+    counter_temporality: DataDogCounterTemporality,
+    preserve_gauges: bool,
+    histogram_aggregates: Option<Vec<DataDogHistogramAggregate>>,
+    histograms: HistogramStores,
+    namespace: Option<String>,
+    idle_expiry: Option<IdleExpiry>,
+    assertions: Option<FlushAssertions>,
+    sources: Vec<Arc<dyn DataDogMetricSource>>,
+    /// Formatted tags of every key seen by the last collection, by key hash
+    tag_cache: Mutex<TagCache>,
+}
+
+impl Collector {
+    pub(crate) fn new(
+        registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+        metadata: MetadataStore,
+        config: &DataDogConfig,
+    ) -> Self {
+        Collector {
+            registry,
+            metadata,
+            metadata_overrides: config.metadata_overrides.clone(),
+            tags: config.tags.clone(),
+            namespace_tags: config.namespace_tags.clone(),
+            unit_tags: config.unit_tags,
+            tag_filters: config.tag_filters.clone(),
+            gauge_aggregation: config.gauge_aggregation,
+            counter_temporality: config.counter_temporality,
+            preserve_gauges: config.preserve_gauges,
+            histogram_aggregates: config.histogram_aggregates.clone(),
+            histograms: config.histograms.clone(),
+            namespace: config.namespace.clone(),
+            idle_expiry: config.idle_timeout.map(IdleExpiry::new),
+            assertions: config
+                .flush_assertions
+                .clone()
+                .map(|callback| FlushAssertions::new(config.gauge_bounds.clone(), callback)),
+            sources: config.sources.clone(),
+            tag_cache: Mutex::default(),
+        }
+    }
+
+    /// Collect metrics
+    ///
+    /// Note: This will clear histogram observations
+    pub(crate) fn collect(&self) -> Vec<DataDogMetric> {
+        self.collect_matching(|_| true)
+    }
+
+    /// Collect metrics whose key matches `filter`, leaving the rest in the registry
+    pub(crate) fn collect_matching(&self, filter: impl Fn(&Key) -> bool) -> Vec<DataDogMetric> {
+        if let Some(idle_expiry) = &self.idle_expiry {
+            self.expire_idle(idle_expiry, &filter);
+        }
+        let mut cached = std::mem::take(&mut *self.tag_cache.lock());
+        let mut seen = HashMap::with_capacity(cached.len());
+
+        let counters = self
+            .registry
+            .get_counter_handles()
+            .into_iter()
+            .filter(|(k, _)| filter(k) && self.accepts(k))
+            .group_by(|(k, _)| k.clone())
+            .into_iter()
+            .map(|(key, values)| {
+                let tags = self.tags(&mut cached, &mut seen, &key);
+                let metric = DataDogMetric::from_counter(
+                    key.clone(),
+                    values
+                        .into_iter()
+                        .map(|(_, v)| v.get_inner().clone())
+                        .collect_vec(),
+                    tags,
+                );
+                match self.counter_temporality {
+                    DataDogCounterTemporality::Delta => metric,
+                    // A running total would be summed up as a count
+                    DataDogCounterTemporality::Cumulative => DataDogMetric {
+                        metric_type: DataDogMetricType::Gauge,
+                        ..metric
+                    },
+                }
+            })
+            .collect_vec();
+
+        let gauges = self
+            .registry
+            .get_gauge_handles()
+            .into_iter()
+            .filter(|(k, _)| filter(k) && self.accepts(k))
+            .group_by(|(k, _)| k.clone())
+            .into_iter()
+            .map(|(key, values)| {
+                let tags = self.tags(&mut cached, &mut seen, &key);
+                let metric = DataDogMetric::from_gauge(
+                    key.clone(),
+                    values
+                        .into_iter()
+                        .map(|(_, v)| v.get_inner().clone())
+                        .collect_vec(),
+                    tags,
+                    self.gauge_aggregation,
+                );
+                if let Some(assertions) = &self.assertions {
+                    assertions.check_gauge(&key, &metric);
+                }
+                metric
+            })
+            .collect_vec();
+
+        #[cfg(feature = "recorder")]
+        let sampled = self
+            .histograms
+            .reservoirs
+            .iter()
+            .flat_map(|reservoirs| reservoirs.take(|k| filter(k)))
+            .filter(|(key, _)| self.accepts(key));
+        #[cfg(not(feature = "recorder"))]
+        let sampled = std::iter::empty();
+        let histograms = self
+            .registry
+            .get_histogram_handles()
+            .into_iter()
+            .filter(|(k, _)| filter(k) && self.accepts(k))
+            .group_by(|(k, _)| k.clone())
+            .into_iter()
+            .map(|(key, values)| {
+                let samples = values
+                    .into_iter()
+                    .flat_map(|(_, v)| v.get_inner().data())
+                    .collect_vec();
+                (key, samples)
+            })
+            .collect_vec()
+            .into_iter()
+            .chain(sampled)
+            .flat_map(|(key, samples)| {
+                let tags = self.tags(&mut cached, &mut seen, &key);
+                match &self.histogram_aggregates {
+                    Some(aggregates) => {
+                        DataDogMetric::from_histogram_aggregates(key, samples, tags, aggregates)
+                    }
+                    None => vec![DataDogMetric::from_histogram(key, samples, tags)],
+                }
+            })
+            .collect_vec();
+
+        #[cfg(feature = "recorder")]
+        let sketched = self
+            .histograms
+            .sketches
+            .iter()
+            .flat_map(|sketches| sketches.take(|k| filter(k)))
+            .filter(|(key, _)| self.accepts(key))
+            .flat_map(|(key, sketch)| {
+                let tags = self.tags(&mut cached, &mut seen, &key);
+                let aggregates = self
+                    .histogram_aggregates
+                    .as_deref()
+                    .unwrap_or(&SKETCH_AGGREGATES);
+                DataDogMetric::from_sketch(key, &sketch, tags, aggregates)
+            })
+            .collect_vec();
+        #[cfg(not(feature = "recorder"))]
+        let sketched = Vec::new();
+
+        if self.counter_temporality == DataDogCounterTemporality::Delta {
+            self.registry.retain_counters(|k, _| !filter(k));
+        }
+        if !self.preserve_gauges {
+            self.registry.retain_gauges(|k, _| !filter(k));
+        }
+        self.registry.retain_histograms(|k, _| !filter(k));
+        // Matching keys that weren't seen are dropped so the cache doesn't outgrow the registry
+        cached.retain(|_, (k, _)| !filter(k));
+        seen.extend(cached);
+        *self.tag_cache.lock() = seen;
+
+        // Sources report running totals with cumulative temporality, which may decrease
+        let source_assertions = self
+            .assertions
+            .as_ref()
+            .filter(|_| self.counter_temporality == DataDogCounterTemporality::Cumulative);
+        let sourced = self
+            .sources
+            .iter()
+            .flat_map(|source| source.collect(&filter))
+            .map(|metric| DataDogMetric {
+                tags: self
+                    .global_tags()
+                    .chain(metric.tags.iter().cloned())
+                    .collect(),
+                ..metric
+            })
+            .inspect(|metric| {
+                if let Some(assertions) = &source_assertions {
+                    assertions.check_counter(metric);
+                }
+            });
+
+        let mut metrics = counters
+            .into_iter()
+            .chain(gauges)
+            .chain(histograms)
+            .chain(sketched)
+            .chain(sourced)
+            .collect_vec();
+
+        if self.unit_tags {
+            self.tag_units(&mut metrics);
+        }
+        if self.namespace.is_some() {
+            for metric in &mut metrics {
+                metric.metric = self.exported_name(&metric.metric);
+            }
+        }
+
+        metrics
+    }
+
+    /// Delete the metrics kept across flushes that stayed idle for the timeout
+    fn expire_idle(&self, idle_expiry: &IdleExpiry, filter: &impl Fn(&Key) -> bool) {
+        if self.counter_temporality == DataDogCounterTemporality::Cumulative {
+            idle_expiry.expire_counters(&self.registry, filter);
+        }
+        if self.preserve_gauges {
+            idle_expiry.expire_gauges(&self.registry, filter);
+        }
+    }
+
+    /// Name `name` is exported as, prefixed by the namespace unless it is one of the exporter's
+    /// own metrics
+    pub(crate) fn exported_name(&self, name: &str) -> String {
+        match &self.namespace {
+            Some(namespace) if !name.starts_with(SELF_METRICS) => format!("{namespace}.{name}"),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Whether `key` passes the tag filters
+    fn accepts(&self, key: &Key) -> bool {
+        self.tag_filters.iter().all(|filter| {
+            let value = key
+                .labels()
+                .chain(self.namespace_labels(key))
+                .chain(&self.tags)
+                .find(|label| label.key() == filter.tag())
+                .map(|label| label.value());
+            filter.accepts(value)
+        })
+    }
+
+    /// Gauge reporting the exporter is alive, tagged with the global tags
+    pub(crate) fn heartbeat(&self) -> DataDogMetric {
+        DataDogMetric {
+            metric: HEARTBEAT_METRIC.to_string(),
+            metric_type: DataDogMetricType::Gauge,
+            points: vec![DataDogMetricValue::Float(1.0)],
+            timestamp: Utc::now().timestamp(),
+            tags: self.global_tags().collect(),
+        }
+    }
+
+    /// Formatted global tags
+    fn global_tags(&self) -> impl Iterator<Item = String> + '_ {
+        self.tags
+            .iter()
+            .map(|l| format!("{}:{}", l.key(), l.value()))
+    }
+
+    /// Number of registered metrics, e.g. left by a filtered collection
+    pub(crate) fn stats(&self, consecutive_empty_flushes: u64) -> DataDogRegistryStats {
+        DataDogRegistryStats {
+            counters: self.registry.get_counter_handles().len(),
+            gauges: self.registry.get_gauge_handles().len(),
+            histograms: self.registry.get_histogram_handles().len() + self.histograms.len(),
+            consecutive_empty_flushes,
+        }
+    }
+
+    /// Number of histogram observations discarded by full reservoirs
+    pub(crate) fn discarded_samples(&self) -> u64 {
+        self.histograms.discarded()
+    }
+
+    /// Set the gauge `name` to `value`, exported with the next collection
+    pub(crate) fn record_gauge(&self, name: &'static str, value: f64) {
+        self.registry
+            .get_or_create_gauge(&Key::from_static_name(name), |gauge| gauge.set(value));
+    }
+
+    /// Record `value` in the histogram `name`, exported with the next collection
+    pub(crate) fn record_histogram(&self, name: &'static str, value: f64) {
+        self.registry
+            .get_or_create_histogram(&Key::from_static_name(name), |histogram| {
+                histogram.record(value)
+            });
+    }
+
+    /// Drop every recorded metric and cached tag set
+    pub(crate) fn reset(&self) {
+        self.registry.clear();
+        self.tag_cache.lock().clear();
+        if let Some(idle_expiry) = &self.idle_expiry {
+            idle_expiry.clear();
+        }
+        if let Some(assertions) = &self.assertions {
+            assertions.clear();
+        }
+        self.histograms.clear();
+    }
+
+    /// Formatted tags of `key`, reused from the previous collection when possible
+    fn tags(&self, cached: &mut TagCache, seen: &mut TagCache, key: &Key) -> Vec<String> {
+        let hash = key.get_hash();
+        if let Some((_, tags)) = seen.get(&hash).filter(|(k, _)| k == key) {
+            return tags.clone();
+        }
+        let entry = cached
+            .remove(&hash)
+            .filter(|(k, _)| k == key)
+            .unwrap_or_else(|| {
+                let tags = self
+                    .tags
+                    .iter()
+                    .chain(self.namespace_labels(key))
+                    .chain(key.labels())
+                    .map(|l| format!("{}:{}", l.key(), l.value()))
+                    .collect();
+                (key.clone(), tags)
+            });
+        let tags = entry.1.clone();
+        // On a hash collision the first key keeps its entry, the other one is formatted each time
+        seen.entry(hash).or_insert(entry);
+        tags
+    }
+
+    /// Tags inherited by `key` from the namespaces enclosing its name
+    fn namespace_labels<'a>(&'a self, key: &Key) -> Vec<&'a Label> {
+        let mut labels: Vec<&Label> = vec![];
+        let name = key.name();
+        for (namespace, tags) in &self.namespace_tags {
+            let enclosed = name
+                .strip_prefix(namespace.as_str())
+                .is_some_and(|rest| rest.starts_with('.'));
+            if enclosed {
+                labels.retain(|label| tags.iter().all(|tag| tag.key() != label.key()));
+                labels.extend(tags);
+            }
+        }
+        labels.retain(|label| key.labels().all(|own| own.key() != label.key()));
+        labels
+    }
+
+    fn tag_units(&self, metrics: &mut [DataDogMetric]) {
+        let metadata = self.metadata.read();
+        for metric in metrics {
+            let unit = self
+                .metadata_overrides
+                .get(&metric.metric)
+                .or_else(|| metadata.get(&metric.metric))
+                .and_then(|m| m.unit.as_ref());
+            if let Some(unit) = unit {
+                metric.tags.push(format!("unit:{}", unit.tag_value()));
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Checks that payloads are acceptable to the DataDog series intake
+//!
+//! Validates payloads against the documented [schema and size
+//! limits](https://docs.datadoghq.com/api/latest/metrics/#submit-metrics), so custom sinks and
+//! serializers can verify their output without a DataDog account.
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use serde_json::Value;
+
+use crate::exporter::{MAX_DECOMPRESSED_PAYLOAD, MAX_PAYLOAD_BYTES};
+use crate::naming::{tag_violation, MAX_NAME_LENGTH};
+use crate::{Error, Result};
+
+const SERIES_TYPES: [&str; 3] = ["count", "rate", "gauge"];
+
+/// Validate a series payload, gzip compressed if `gzip`
+///
+/// Fails with [`Error::NonCompliantPayload`] listing every violation found
+pub fn validate(payload: &[u8], gzip: bool) -> Result<()> {
+    let mut violations = vec![];
+    if payload.len() >= MAX_PAYLOAD_BYTES {
+        violations.push(format!(
+            "payload is {} bytes, limit is {MAX_PAYLOAD_BYTES}",
+            payload.len()
+        ));
+    }
+
+    let body = if gzip {
+        let mut body = vec![];
+        GzDecoder::new(payload)
+            .take(MAX_DECOMPRESSED_PAYLOAD as u64 + 1)
+            .read_to_end(&mut body)?;
+        body
+    } else {
+        payload.to_vec()
+    };
+    if body.len() > MAX_DECOMPRESSED_PAYLOAD {
+        violations.push(format!(
+            "decompressed payload exceeds {MAX_DECOMPRESSED_PAYLOAD} bytes"
+        ));
+    }
+
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(value) => validate_post(&value, &mut violations),
+        Err(e) => violations.push(format!("payload is not JSON: {e}")),
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::NonCompliantPayload { violations })
+    }
+}
+
+fn validate_post(post: &Value, violations: &mut Vec<String>) {
+    let Some(series) = post.get("series").and_then(Value::as_array) else {
+        violations.push("`series` is not an array".to_string());
+        return;
+    };
+    for (i, series) in series.iter().enumerate() {
+        validate_series(series, &format!("series[{i}]"), violations);
+    }
+}
+
+fn validate_series(series: &Value, path: &str, violations: &mut Vec<String>) {
+    match series.get("metric").and_then(Value::as_str) {
+        Some("") => violations.push(format!("{path}.metric is empty")),
+        Some(metric) if metric.len() > MAX_NAME_LENGTH => violations.push(format!(
+            "{path}.metric is longer than {MAX_NAME_LENGTH} characters"
+        )),
+        Some(_) => {}
+        None => violations.push(format!("{path}.metric is not a string")),
+    }
+
+    match series.get("points").and_then(Value::as_array) {
+        Some(points) if points.is_empty() => violations.push(format!("{path}.points is empty")),
+        Some(points) => {
+            for (i, point) in points.iter().enumerate() {
+                let valid = point.as_array().is_some_and(|point| {
+                    point.len() == 2 && point[0].is_i64() && point[1].is_number()
+                });
+                if !valid {
+                    violations.push(format!(
+                        "{path}.points[{i}] is not a [timestamp, value] pair"
+                    ));
+                }
+            }
+        }
+        None => violations.push(format!("{path}.points is not an array")),
+    }
+
+    if let Some(metric_type) = series.get("type") {
+        let known = metric_type
+            .as_str()
+            .is_some_and(|t| SERIES_TYPES.contains(&t));
+        if !known {
+            violations.push(format!("{path}.type {metric_type} is not a series type"));
+        }
+    }
+
+    if let Some(interval) = series.get("interval") {
+        if !interval.is_null() && !interval.is_i64() {
+            violations.push(format!("{path}.interval is not an integer"));
+        }
+    }
+
+    match series.get("tags") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(tags)) => {
+            for (i, tag) in tags.iter().enumerate() {
+                let Some(tag) = tag.as_str() else {
+                    violations.push(format!("{path}.tags[{i}] is not a string"));
+                    continue;
+                };
+                let (key, value) = match tag.split_once(':') {
+                    Some((key, value)) => (key, Some(value)),
+                    None => (tag, None),
+                };
+                if let Some(reason) = tag_violation(key, value) {
+                    violations.push(format!("{path}.tags[{i}] {reason}"));
+                }
+            }
+        }
+        Some(_) => violations.push(format!("{path}.tags is not an array")),
+    }
+}
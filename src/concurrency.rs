@@ -0,0 +1,42 @@
+//! Request concurrency shared by the API hosts
+
+use itertools::Itertools;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limit on concurrent requests split between the API hosts by weight, so a slow host can't
+/// hold the requests meant for the others
+pub(crate) struct RequestSlots {
+    limits: Vec<usize>,
+    hosts: Vec<Semaphore>,
+}
+
+impl RequestSlots {
+    /// Split `max_requests` between `hosts` API hosts, weighted by `weights` primary first with
+    /// missing weights counting as 1
+    ///
+    /// Every host gets at least one slot
+    pub(crate) fn new(max_requests: usize, weights: &[u32], hosts: usize) -> Self {
+        let weights = (0..hosts)
+            .map(|host| weights.get(host).copied().unwrap_or(1) as usize)
+            .collect_vec();
+        let total = weights.iter().sum::<usize>().max(1);
+        let limits = weights
+            .iter()
+            .map(|weight| (max_requests * weight / total).max(1))
+            .collect_vec();
+        RequestSlots {
+            hosts: limits.iter().map(|limit| Semaphore::new(*limit)).collect(),
+            limits,
+        }
+    }
+
+    /// Number of concurrent requests allowed to `host`
+    pub(crate) fn limit(&self, host: usize) -> Option<usize> {
+        self.limits.get(host).copied()
+    }
+
+    /// Wait for a free slot of `host`, held until the permit is dropped
+    pub(crate) async fn acquire(&self, host: usize) -> Option<SemaphorePermit<'_>> {
+        self.hosts.get(host)?.acquire().await.ok()
+    }
+}
@@ -1,17 +1,24 @@
 //! Data model
 //!
+#[cfg(feature = "exporter")]
 use std::sync::atomic::Ordering;
+#[cfg(feature = "exporter")]
 use std::sync::Arc;
 
+#[cfg(feature = "exporter")]
 use chrono::Utc;
 use itertools::Itertools;
+#[cfg(feature = "exporter")]
 use metrics::atomics::AtomicU64;
-use metrics::{Key, Label};
-use metrics_util::AtomicBucket;
+#[cfg(feature = "exporter")]
+use metrics::Key;
 
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
+#[cfg(all(feature = "recorder", feature = "exporter"))]
+use crate::sketch::DDSketch;
+
 /// Metric type
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, PartialOrd, Ord)]
 pub enum DataDogMetricType {
@@ -26,6 +33,216 @@ pub enum DataDogMetricType {
     Histogram,
 }
 
+/// Series type submitted to DataDog
+///
+/// Distributions are not a series type, see
+/// [`DataDogBuilder::distributions`](crate::DataDogBuilder::distributions).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
+pub enum DataDogSeriesType {
+    /// Count
+    #[serde(rename = "count")]
+    Count,
+    /// Rate
+    #[serde(rename = "rate")]
+    Rate,
+    /// Gauge
+    #[serde(rename = "gauge")]
+    Gauge,
+}
+
+/// Series type submitted for each metric type
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DataDogSeriesTypePolicy {
+    /// Series type of counters
+    pub counter: DataDogSeriesType,
+    /// Series type of gauges
+    pub gauge: DataDogSeriesType,
+    /// Series type of histogram observations
+    pub histogram: DataDogSeriesType,
+}
+
+impl Default for DataDogSeriesTypePolicy {
+    fn default() -> Self {
+        DataDogSeriesTypePolicy {
+            counter: DataDogSeriesType::Count,
+            gauge: DataDogSeriesType::Gauge,
+            histogram: DataDogSeriesType::Gauge,
+        }
+    }
+}
+
+impl DataDogSeriesTypePolicy {
+    /// Series type submitted for `metric_type`
+    pub fn series_type(&self, metric_type: &DataDogMetricType) -> DataDogSeriesType {
+        match metric_type {
+            DataDogMetricType::Count => self.counter,
+            DataDogMetricType::Gauge => self.gauge,
+            DataDogMetricType::Histogram => self.histogram,
+        }
+    }
+}
+
+/// How the values collected for a gauge during a flush are combined into one point
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DataDogGaugeAggregation {
+    /// Last value collected
+    Last,
+    /// Smallest value
+    Min,
+    /// Largest value
+    Max,
+    /// Arithmetic mean of the values
+    Mean,
+    /// Sum of the values
+    Sum,
+}
+
+impl DataDogGaugeAggregation {
+    /// Combine `values`, [`None`] if there are none
+    pub fn aggregate(&self, values: &[f64]) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+        let aggregate = match self {
+            DataDogGaugeAggregation::Last => values[values.len() - 1],
+            DataDogGaugeAggregation::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            DataDogGaugeAggregation::Max => {
+                values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+            }
+            DataDogGaugeAggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            DataDogGaugeAggregation::Sum => values.iter().sum(),
+        };
+        Some(aggregate)
+    }
+}
+
+/// Value computed from the observations of a histogram during a flush
+///
+/// Submitted as a metric named after the histogram with a suffix, e.g. `latency.p99`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataDogHistogramAggregate {
+    /// Number of observations, suffixed `count` and submitted as a count
+    Count,
+    /// Sum of the observations, suffixed `sum`
+    Sum,
+    /// Smallest observation, suffixed `min`
+    Min,
+    /// Largest observation, suffixed `max`
+    Max,
+    /// Arithmetic mean of the observations, suffixed `avg`
+    Avg,
+    /// Percentile of the observations between 0 and 100, e.g. `Percentile(99.9)` suffixed `p999`
+    Percentile(f64),
+}
+
+impl DataDogHistogramAggregate {
+    /// Suffix added to the histogram name
+    pub fn suffix(&self) -> String {
+        match self {
+            DataDogHistogramAggregate::Count => "count".to_string(),
+            DataDogHistogramAggregate::Sum => "sum".to_string(),
+            DataDogHistogramAggregate::Min => "min".to_string(),
+            DataDogHistogramAggregate::Max => "max".to_string(),
+            DataDogHistogramAggregate::Avg => "avg".to_string(),
+            DataDogHistogramAggregate::Percentile(percentile) => {
+                format!("p{}", percentile.clamp(0.0, 100.0)).replace('.', "")
+            }
+        }
+    }
+
+    /// Combine `sorted` observations, [`None`] if there are none
+    #[cfg(feature = "exporter")]
+    fn aggregate_sorted(&self, sorted: &[f64]) -> Option<f64> {
+        let (first, last) = (sorted.first()?, sorted.last()?);
+        let aggregate = match self {
+            DataDogHistogramAggregate::Count => sorted.len() as f64,
+            DataDogHistogramAggregate::Sum => sorted.iter().sum(),
+            DataDogHistogramAggregate::Min => *first,
+            DataDogHistogramAggregate::Max => *last,
+            DataDogHistogramAggregate::Avg => sorted.iter().sum::<f64>() / sorted.len() as f64,
+            DataDogHistogramAggregate::Percentile(percentile) => {
+                sorted[nearest_rank(*percentile, sorted.len() as u64) as usize - 1]
+            }
+        };
+        Some(aggregate)
+    }
+
+    /// Estimate from `sketch`, [`None`] if it is empty
+    #[cfg(all(feature = "recorder", feature = "exporter"))]
+    pub(crate) fn aggregate_sketch(&self, sketch: &DDSketch) -> Option<f64> {
+        match self {
+            DataDogHistogramAggregate::Count => Some(sketch.count() as f64),
+            DataDogHistogramAggregate::Sum => sketch.min().map(|_| sketch.sum()),
+            DataDogHistogramAggregate::Min => sketch.min(),
+            DataDogHistogramAggregate::Max => sketch.max(),
+            DataDogHistogramAggregate::Avg => {
+                sketch.min().map(|_| sketch.sum() / sketch.count() as f64)
+            }
+            DataDogHistogramAggregate::Percentile(percentile) => {
+                sketch.value_at_rank(nearest_rank(*percentile, sketch.count()))
+            }
+        }
+    }
+}
+
+/// 1-based rank of `percentile` among `count` observations
+#[cfg(feature = "exporter")]
+fn nearest_rank(percentile: f64, count: u64) -> u64 {
+    let rank = (percentile.clamp(0.0, 100.0) / 100.0 * count as f64).ceil() as u64;
+    rank.clamp(1, count.max(1))
+}
+
+/// What counter values cover
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DataDogCounterTemporality {
+    /// Increments since the previous flush, counters are reset on every flush
+    #[default]
+    Delta,
+    /// Total since the counter was registered, reported as a gauge and never reset
+    Cumulative,
+}
+
+/// Predicate on a tag value deciding whether a series is collected
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DataDogTagFilter {
+    /// Drop series tagged `tag` with any of `values`, e.g. `endpoint:/healthz`
+    Drop {
+        /// Tag name
+        tag: String,
+        /// Tag values to drop
+        values: Vec<String>,
+    },
+    /// Keep only series tagged `tag` with one of `values`, e.g. `env:prod`
+    Keep {
+        /// Tag name
+        tag: String,
+        /// Tag values to keep
+        values: Vec<String>,
+    },
+}
+
+impl DataDogTagFilter {
+    /// Name of the filtered tag
+    pub fn tag(&self) -> &str {
+        match self {
+            DataDogTagFilter::Drop { tag, .. } | DataDogTagFilter::Keep { tag, .. } => tag,
+        }
+    }
+
+    /// Whether a series with `value` for the filtered tag, [`None`] if it doesn't have the tag,
+    /// is collected
+    pub fn accepts(&self, value: Option<&str>) -> bool {
+        match self {
+            DataDogTagFilter::Drop { values, .. } => {
+                value.is_none_or(|value| !values.iter().any(|v| v == value))
+            }
+            DataDogTagFilter::Keep { values, .. } => {
+                value.is_some_and(|value| values.iter().any(|v| v == value))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialOrd, PartialEq)]
 #[serde(untagged)]
 /// Metric value
@@ -36,6 +253,16 @@ pub enum DataDogMetricValue {
     Unsigned(u64),
 }
 
+impl DataDogMetricValue {
+    /// Value as a float
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            DataDogMetricValue::Float(value) => *value,
+            DataDogMetricValue::Unsigned(value) => *value as f64,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialOrd, PartialEq)]
 /// DataDog formatted metric
 pub struct DataDogMetric {
@@ -51,12 +278,9 @@ pub struct DataDogMetric {
     pub tags: Vec<String>,
 }
 
+#[cfg(feature = "exporter")]
 impl DataDogMetric {
-    pub(crate) fn from_counter(
-        key: Key,
-        values: Vec<Arc<AtomicU64>>,
-        global_tags: &[Label],
-    ) -> Self {
+    pub(crate) fn from_counter(key: Key, values: Vec<Arc<AtomicU64>>, tags: Vec<String>) -> Self {
         let values = values
             .into_iter()
             .map(|value| {
@@ -64,48 +288,109 @@ impl DataDogMetric {
                 DataDogMetricValue::Unsigned(u)
             })
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Count, key, values, global_tags)
+        DataDogMetric::from_metric_value(DataDogMetricType::Count, key, values, tags)
     }
 
-    pub(crate) fn from_gauge(key: Key, values: Vec<Arc<AtomicU64>>, global_tags: &[Label]) -> Self {
+    pub(crate) fn from_gauge(
+        key: Key,
+        values: Vec<Arc<AtomicU64>>,
+        tags: Vec<String>,
+        aggregation: Option<DataDogGaugeAggregation>,
+    ) -> Self {
         let values = values
             .into_iter()
-            .map(|value| {
-                let u = f64::from_bits(value.load(Ordering::Acquire));
-                DataDogMetricValue::Float(u)
-            })
+            .map(|value| f64::from_bits(value.load(Ordering::Acquire)))
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Gauge, key, values, global_tags)
+        let values = match aggregation {
+            Some(aggregation) => aggregation.aggregate(&values).into_iter().collect_vec(),
+            None => values,
+        }
+        .into_iter()
+        .map(DataDogMetricValue::Float)
+        .collect_vec();
+        DataDogMetric::from_metric_value(DataDogMetricType::Gauge, key, values, tags)
     }
 
-    pub(crate) fn from_histogram(
-        key: Key,
-        values: Vec<Arc<AtomicBucket<f64>>>,
-        global_tags: &[Label],
-    ) -> Self {
-        let values = values
+    pub(crate) fn from_histogram(key: Key, samples: Vec<f64>, tags: Vec<String>) -> Self {
+        let values = samples
             .into_iter()
-            .flat_map(|value| value.data().into_iter().map(DataDogMetricValue::Float))
+            .map(DataDogMetricValue::Float)
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Histogram, key, values, global_tags)
+        DataDogMetric::from_metric_value(DataDogMetricType::Histogram, key, values, tags)
+    }
+
+    /// One metric per aggregate of the histogram observations, none if there are none
+    pub(crate) fn from_histogram_aggregates(
+        key: Key,
+        mut samples: Vec<f64>,
+        tags: Vec<String>,
+        aggregates: &[DataDogHistogramAggregate],
+    ) -> Vec<Self> {
+        samples.sort_by(f64::total_cmp);
+        let sorted = samples;
+        DataDogMetric::from_aggregates(key, tags, aggregates, sorted.len() as u64, |aggregate| {
+            aggregate.aggregate_sorted(&sorted)
+        })
+    }
+
+    /// One metric per aggregate estimated from a histogram sketch, none if it is empty
+    #[cfg(all(feature = "recorder", feature = "exporter"))]
+    pub(crate) fn from_sketch(
+        key: Key,
+        sketch: &DDSketch,
+        tags: Vec<String>,
+        aggregates: &[DataDogHistogramAggregate],
+    ) -> Vec<Self> {
+        DataDogMetric::from_aggregates(key, tags, aggregates, sketch.count(), |aggregate| {
+            aggregate.aggregate_sketch(sketch)
+        })
+    }
+
+    fn from_aggregates(
+        key: Key,
+        tags: Vec<String>,
+        aggregates: &[DataDogHistogramAggregate],
+        count: u64,
+        aggregate: impl Fn(&DataDogHistogramAggregate) -> Option<f64>,
+    ) -> Vec<Self> {
+        if count == 0 {
+            return vec![];
+        }
+        let timestamp = Utc::now().timestamp();
+        aggregates
+            .iter()
+            .filter_map(|kind| {
+                let value = aggregate(kind)?;
+                let (metric_type, point) = match kind {
+                    DataDogHistogramAggregate::Count => (
+                        DataDogMetricType::Count,
+                        DataDogMetricValue::Unsigned(count),
+                    ),
+                    _ => (DataDogMetricType::Gauge, DataDogMetricValue::Float(value)),
+                };
+                Some(DataDogMetric {
+                    metric: format!("{}.{}", key.name(), kind.suffix()),
+                    metric_type,
+                    points: vec![point],
+                    timestamp,
+                    tags: tags.clone(),
+                })
+            })
+            .collect()
     }
 
     fn from_metric_value(
         metric_type: DataDogMetricType,
         key: Key,
         values: Vec<DataDogMetricValue>,
-        global_tags: &[Label],
+        tags: Vec<String>,
     ) -> Self {
         DataDogMetric {
             metric: key.name().to_string(),
             metric_type,
             points: values,
             timestamp: Utc::now().timestamp(),
-            tags: global_tags
-                .iter()
-                .chain(key.labels())
-                .map(|l| format!("{}:{}", l.key(), l.value()))
-                .collect(),
+            tags,
         }
     }
 
@@ -146,6 +431,18 @@ pub struct DataDogApiPost<'a> {
     pub series: &'a [DataDogSeries],
 }
 
+/// DataDog API Response Body
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DataDogApiResponse {
+    /// Errors reported for the submitted payload
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// Most points submitted in a single series, metrics with more values are split into several
+/// series
+pub const MAX_SERIES_POINTS: usize = 1000;
+
 /// DataDog Metric Series
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -158,23 +455,241 @@ pub struct DataDogSeries {
     pub points: Vec<(i64, DataDogMetricValue)>,
     /// Metric tags
     pub tags: Vec<String>,
-    /// Metric type
+    /// Series type
     #[serde(rename = "type")]
-    pub metric_type: DataDogMetricType,
+    pub metric_type: DataDogSeriesType,
+    /// Host the series is reported for
+    pub host: Option<String>,
 }
 
 impl DataDogSeries {
-    /// Create metric series from metric
+    /// Create metric series from metric, typed by the default [`DataDogSeriesTypePolicy`]
     pub fn new(m: DataDogMetric) -> Vec<DataDogSeries> {
+        DataDogSeries::with_policy(m, &DataDogSeriesTypePolicy::default())
+    }
+
+    /// Create metric series from metric, typed by `policy`
+    ///
+    /// All values of the metric share its name, tags and timestamp, so they are batched as the
+    /// points of a single series, split into series of at most [`MAX_SERIES_POINTS`] points.
+    /// Series too large for a payload are split again with [`DataDogSeries::split_points`]. A
+    /// metric without values has no series.
+    pub fn with_policy(m: DataDogMetric, policy: &DataDogSeriesTypePolicy) -> Vec<DataDogSeries> {
+        DataDogSeries::from_metric(&m, policy)
+    }
+
+    /// Series of a borrowed metric, see [`DataDogSeries::with_policy`]
+    pub(crate) fn from_metric(
+        m: &DataDogMetric,
+        policy: &DataDogSeriesTypePolicy,
+    ) -> Vec<DataDogSeries> {
+        let metric_type = policy.series_type(&m.metric_type);
+        let timestamp = m.timestamp;
+        let tags = m.tags.clone();
         m.points
-            .chunks(3)
+            .iter()
+            .map(|v| (timestamp, v.clone()))
+            .chunks(MAX_SERIES_POINTS)
+            .into_iter()
             .map(|points| DataDogSeries {
                 interval: None,
-                metric: m.metric.to_owned(),
-                points: points.iter().map(|v| (m.timestamp, v.to_owned())).collect(),
-                tags: m.tags.to_owned(),
-                metric_type: m.metric_type.to_owned(),
+                metric: m.metric.clone(),
+                points: points.collect(),
+                tags: tags.clone(),
+                metric_type,
+                host: None,
             })
-            .collect_vec()
+            .collect()
+    }
+
+    /// Shard of this series among `shards`, stable for a given name and tag set
+    pub fn shard(&self, shards: usize) -> usize {
+        // FNV-1a, so shards don't change between builds or processes
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+        let hash = std::iter::once(&self.metric)
+            .chain(&self.tags)
+            .flat_map(|s| s.bytes().chain(std::iter::once(0)))
+            .fold(FNV_OFFSET, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            });
+        (hash % shards.max(1) as u64) as usize
+    }
+
+    /// Split the points of this series into two series sharing its name, tags and type
+    ///
+    /// Returns [`None`] if the series has fewer than two points
+    pub fn split_points(&self) -> Option<(DataDogSeries, DataDogSeries)> {
+        if self.points.len() < 2 {
+            return None;
+        }
+        let with_points = |points: &[(i64, DataDogMetricValue)]| DataDogSeries {
+            interval: self.interval,
+            metric: self.metric.clone(),
+            points: points.to_vec(),
+            tags: self.tags.clone(),
+            metric_type: self.metric_type,
+            host: self.host.clone(),
+        };
+        let (left, right) = self.points.split_at(self.points.len() / 2);
+        Some((with_points(left), with_points(right)))
+    }
+}
+
+/// Version of the DataDog metrics API series are submitted to
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum DataDogApiVersion {
+    /// `/api/v1/series`, submitting [`DataDogSeries`]
+    #[default]
+    V1,
+    /// `/api/v2/series`, submitting [`DataDogSeriesV2`]
+    V2,
+}
+
+/// DataDog API v2 Post Body
+#[derive(Debug, Serialize, Clone)]
+pub struct DataDogApiPostV2 {
+    /// Metric series
+    pub series: Vec<DataDogSeriesV2>,
+}
+
+/// DataDog API v2 Metric Series
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataDogSeriesV2 {
+    /// Metric name
+    pub metric: String,
+    /// Intake type: 0 unspecified, 1 count, 2 rate, 3 gauge
+    #[serde(rename = "type")]
+    pub metric_type: u8,
+    /// Metric time series
+    pub points: Vec<DataDogPointV2>,
+    /// Metric tags, without the `host` tag
+    pub tags: Vec<String>,
+    /// Resources the series is attached to, from the host of the series and its `host` tag
+    pub resources: Vec<DataDogResourceV2>,
+    /// Metric interval
+    pub interval: Option<i64>,
+}
+
+/// DataDog API v2 point
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DataDogPointV2 {
+    /// Seconds since the Unix epoch
+    pub timestamp: i64,
+    /// Point value
+    pub value: f64,
+}
+
+/// DataDog API v2 resource
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DataDogResourceV2 {
+    /// Resource name
+    pub name: String,
+    /// Resource type, e.g. `host`
+    #[serde(rename = "type")]
+    pub resource_type: String,
+}
+
+impl From<&DataDogSeries> for DataDogSeriesV2 {
+    fn from(series: &DataDogSeries) -> Self {
+        let (hosts, tags): (Vec<_>, Vec<_>) =
+            series.tags.iter().partition(|tag| tag.starts_with("host:"));
+        DataDogSeriesV2 {
+            metric: series.metric.clone(),
+            metric_type: match series.metric_type {
+                DataDogSeriesType::Count => 1,
+                DataDogSeriesType::Rate => 2,
+                DataDogSeriesType::Gauge => 3,
+            },
+            points: series
+                .points
+                .iter()
+                .map(|(timestamp, value)| DataDogPointV2 {
+                    timestamp: *timestamp,
+                    value: value.as_f64(),
+                })
+                .collect(),
+            tags: tags.into_iter().cloned().collect(),
+            resources: series
+                .host
+                .iter()
+                .map(String::as_str)
+                .chain(hosts.into_iter().map(|host| &host["host:".len()..]))
+                .map(|host| DataDogResourceV2 {
+                    name: host.to_string(),
+                    resource_type: "host".to_string(),
+                })
+                .collect(),
+            interval: series.interval,
+        }
+    }
+}
+
+/// DataDog Distribution Points API Post Body
+#[derive(Debug, Serialize, Clone)]
+pub struct DataDogDistributionPost<'a> {
+    /// Distributions
+    pub series: &'a [DataDogDistribution],
+}
+
+/// DataDog distribution, aggregated server-side into percentiles
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataDogDistribution {
+    /// Metric name
+    pub metric: String,
+    /// Observations, grouped by timestamp
+    pub points: Vec<(i64, Vec<f64>)>,
+    /// Metric tags
+    pub tags: Vec<String>,
+}
+
+impl DataDogDistribution {
+    /// Create a distribution from the observations of a metric
+    pub fn new(m: DataDogMetric) -> Self {
+        DataDogDistribution::from_metric(&m)
+    }
+
+    /// Distribution of a borrowed metric, see [`DataDogDistribution::new`]
+    pub(crate) fn from_metric(m: &DataDogMetric) -> Self {
+        DataDogDistribution {
+            points: vec![(
+                m.timestamp,
+                m.points.iter().map(DataDogMetricValue::as_f64).collect(),
+            )],
+            metric: m.metric.clone(),
+            tags: m.tags.clone(),
+        }
+    }
+
+    /// Split the observations of this distribution into two distributions sharing its name and
+    /// tags
+    ///
+    /// Returns [`None`] if the distribution has fewer than two observations
+    pub fn split_points(&self) -> Option<(DataDogDistribution, DataDogDistribution)> {
+        let (timestamp, values) = match self.points.as_slice() {
+            [(timestamp, values)] if values.len() >= 2 => (*timestamp, values),
+            [_] | [] => return None,
+            points => {
+                let (left, right) = points.split_at(points.len() / 2);
+                return Some((
+                    self.with_points(left.to_vec()),
+                    self.with_points(right.to_vec()),
+                ));
+            }
+        };
+        let (left, right) = values.split_at(values.len() / 2);
+        Some((
+            self.with_points(vec![(timestamp, left.to_vec())]),
+            self.with_points(vec![(timestamp, right.to_vec())]),
+        ))
+    }
+
+    fn with_points(&self, points: Vec<(i64, Vec<f64>)>) -> DataDogDistribution {
+        DataDogDistribution {
+            metric: self.metric.clone(),
+            points,
+            tags: self.tags.clone(),
+        }
     }
 }
@@ -0,0 +1,121 @@
+//! Conversions between [`DataDogMetric`] and the snapshots of the `metrics-util`
+//! [`DebuggingRecorder`](metrics_util::debugging::DebuggingRecorder)
+//!
+//! Tests already recording with the debugging recorder can assert on the metrics this crate would
+//! export for the same observations, and compare exported metrics with snapshot entries.
+//!
+//! ```
+//! use metrics::{counter, with_local_recorder};
+//! use metrics_datadog_exporter::debugging::from_snapshot;
+//! use metrics_datadog_exporter::DataDogMetricValue;
+//! use metrics_util::debugging::DebuggingRecorder;
+//!
+//! let recorder = DebuggingRecorder::new();
+//! let snapshotter = recorder.snapshotter();
+//! with_local_recorder(&recorder, || counter!("requests", "route" => "/").increment(2));
+//!
+//! let metrics = from_snapshot(snapshotter.snapshot(), &["env:test".to_string()]);
+//! assert_eq!(metrics[0].metric, "requests");
+//! assert_eq!(metrics[0].tags, vec!["env:test".to_string(), "route:/".to_string()]);
+//! assert_eq!(metrics[0].points, [DataDogMetricValue::Unsigned(2)]);
+//! ```
+
+use chrono::Utc;
+use itertools::Itertools;
+use metrics::{Key, Label};
+use metrics_util::debugging::{DebugValue, Snapshot};
+use metrics_util::{CompositeKey, MetricKind};
+use ordered_float::OrderedFloat;
+
+use crate::data::{DataDogMetric, DataDogMetricType, DataDogMetricValue};
+
+/// Metrics exported for the entries of `snapshot`, with `tags` sent before the labels of each
+///
+/// Counters become [`DataDogMetricType::Count`], gauges [`DataDogMetricType::Gauge`] and
+/// histograms [`DataDogMetricType::Histogram`] with one point per observation, as exported with
+/// the default settings.
+pub fn from_snapshot(snapshot: Snapshot, tags: &[String]) -> Vec<DataDogMetric> {
+    let timestamp = Utc::now().timestamp();
+    snapshot
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let (metric_type, points) = match value {
+                DebugValue::Counter(value) => (
+                    DataDogMetricType::Count,
+                    vec![DataDogMetricValue::Unsigned(value)],
+                ),
+                DebugValue::Gauge(value) => (
+                    DataDogMetricType::Gauge,
+                    vec![DataDogMetricValue::Float(value.0)],
+                ),
+                DebugValue::Histogram(values) => (
+                    DataDogMetricType::Histogram,
+                    values
+                        .into_iter()
+                        .map(|value| DataDogMetricValue::Float(value.0))
+                        .collect_vec(),
+                ),
+            };
+            let key = key.key();
+            DataDogMetric {
+                metric: key.name().to_string(),
+                metric_type,
+                points,
+                timestamp,
+                tags: tags
+                    .iter()
+                    .cloned()
+                    .chain(key.labels().map(|l| format!("{}:{}", l.key(), l.value())))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Snapshot entry holding the value of `metric`, to compare with
+/// [`Snapshot::into_vec`] or [`Snapshot::into_hashmap`]
+///
+/// Tags become labels split at their first `:`, tags without one become labels with an empty
+/// value. Counts are summed and gauges keep their last point.
+pub fn to_snapshot_entry(metric: &DataDogMetric) -> (CompositeKey, DebugValue) {
+    let labels = metric
+        .tags
+        .iter()
+        .map(|tag| {
+            let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+            Label::new(key.to_string(), value.to_string())
+        })
+        .collect_vec();
+    let key = Key::from_parts(metric.metric.clone(), labels);
+    let floats = || {
+        metric
+            .points
+            .iter()
+            .map(|point| OrderedFloat(point.as_f64()))
+    };
+    match metric.metric_type {
+        DataDogMetricType::Count => {
+            let count = metric
+                .points
+                .iter()
+                .map(|point| match point {
+                    DataDogMetricValue::Unsigned(value) => *value,
+                    DataDogMetricValue::Float(value) => *value as u64,
+                })
+                .sum();
+            (
+                CompositeKey::new(MetricKind::Counter, key),
+                DebugValue::Counter(count),
+            )
+        }
+        DataDogMetricType::Gauge => (
+            CompositeKey::new(MetricKind::Gauge, key),
+            DebugValue::Gauge(floats().next_back().unwrap_or_default()),
+        ),
+        DataDogMetricType::Histogram => (
+            CompositeKey::new(MetricKind::Histogram, key),
+            DebugValue::Histogram(floats().collect()),
+        ),
+    }
+}
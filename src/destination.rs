@@ -0,0 +1,90 @@
+//! Additional DataDog organizations the same metrics are submitted to
+
+use tracing::warn;
+
+use crate::exporter::DataDogExporter;
+use crate::sink::DataDogPayload;
+use crate::site::DataDogSite;
+use crate::status::{DataDogDestinationStatus, DataDogFlushStats};
+
+/// DataDog organization metrics are also submitted to, set with
+/// [`DataDogBuilder::destination`](crate::DataDogBuilder::destination), e.g. while migrating to
+/// another organization
+///
+/// ```
+/// # use metrics_datadog_exporter::{DataDogBuilder, DataDogDestination, DataDogSite};
+/// let builder = DataDogBuilder::default()
+///     .write_to_api(true, Some("US1_API_KEY".to_string()))
+///     .destination(DataDogDestination::new("EU1_API_KEY").site(DataDogSite::EU1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDogDestination {
+    pub(crate) api_key: String,
+    pub(crate) api_host: String,
+    pub(crate) fallback_api_hosts: Vec<String>,
+}
+
+impl DataDogDestination {
+    /// Organization of `api_key`, on the default site
+    pub fn new(api_key: impl Into<String>) -> Self {
+        DataDogDestination {
+            api_key: api_key.into(),
+            api_host: DataDogSite::default().api_host(),
+            fallback_api_hosts: vec![],
+        }
+    }
+
+    /// Set the API host
+    #[must_use]
+    pub fn api_host(self, api_host: String) -> Self {
+        DataDogDestination { api_host, ..self }
+    }
+
+    /// Send to the API host of `site`
+    #[must_use]
+    pub fn site(self, site: DataDogSite) -> Self {
+        self.api_host(site.api_host())
+    }
+
+    /// Set API hosts to fail over to, in order, when the API host is unreachable
+    #[must_use]
+    pub fn fallback_api_hosts(self, fallback_api_hosts: Vec<String>) -> Self {
+        DataDogDestination {
+            fallback_api_hosts,
+            ..self
+        }
+    }
+}
+
+/// Submits flushed metrics to a destination with an exporter of its own, so it retries, fails
+/// over and defers payloads while rate limited independently of the other destinations
+pub(crate) struct ApiDestination {
+    api_host: String,
+    exporter: DataDogExporter,
+}
+
+impl ApiDestination {
+    pub(crate) fn new(api_host: String, exporter: DataDogExporter) -> Self {
+        ApiDestination { api_host, exporter }
+    }
+
+    /// Submit the payloads of a flush, failures are logged and kept in the destination status
+    ///
+    /// API keys routed to belong to the primary organization, every payload is submitted with
+    /// the API key of the destination.
+    pub(crate) async fn submit(&self, payloads: &[DataDogPayload]) {
+        let mut stats = DataDogFlushStats::default();
+        let result = self.exporter.write_to_api(payloads, &mut stats).await;
+        if let Err(e) = &result {
+            warn!(api_host = %self.api_host, error = ?e, "Failed to write metrics to a destination");
+        }
+        self.exporter.record_status(&result, stats);
+    }
+
+    pub(crate) fn status(&self) -> DataDogDestinationStatus {
+        DataDogDestinationStatus {
+            api_host: self.api_host.clone(),
+            status: self.exporter.status(),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! DogStatsD transport
+//!
+//! Writes metrics in the [DogStatsD
+//! protocol](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell) to a local agent,
+//! which aggregates them and holds the API key.
+
+use std::io;
+use std::net::UdpSocket;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use parking_lot::Mutex;
+#[cfg(unix)]
+use tracing::debug;
+
+use crate::data::{DataDogMetric, DataDogMetricType, DataDogMetricValue};
+use crate::Result;
+
+/// Default address of the agent's DogStatsD server
+pub(crate) const DEFAULT_ADDR: &str = "127.0.0.1:8125";
+/// Default path of the agent's DogStatsD socket
+#[cfg(unix)]
+pub(crate) const DEFAULT_SOCKET: &str = "/var/run/datadog/dsd.socket";
+/// Largest UDP datagram sent, fitting in a single packet on common MTUs
+const MAX_UDP_DATAGRAM: usize = 1432;
+/// Largest UDS datagram sent, the agent's default receive buffer size
+#[cfg(unix)]
+const MAX_UDS_DATAGRAM: usize = 8192;
+/// Time to wait for room in the socket buffer when the agent falls behind
+#[cfg(unix)]
+const UDS_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends metrics to a DogStatsD server
+pub(crate) struct DogStatsD {
+    /// Shared with the blocking tasks sending the datagrams
+    transport: Arc<Transport>,
+    /// Submit histogram observations as distributions
+    distributions: bool,
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    /// Connected lazily, and again after a failed send, e.g. when the agent restarted
+    #[cfg(unix)]
+    Uds {
+        path: PathBuf,
+        socket: Mutex<Option<UnixDatagram>>,
+    },
+}
+
+impl DogStatsD {
+    /// Connect a UDP socket to `addr`
+    pub(crate) fn udp(addr: &str, distributions: bool) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        Ok(DogStatsD {
+            transport: Arc::new(Transport::Udp(socket)),
+            distributions,
+        })
+    }
+
+    /// Send to the Unix domain socket at `path`, connected on first write
+    #[cfg(unix)]
+    pub(crate) fn uds(path: impl Into<PathBuf>, distributions: bool) -> Self {
+        DogStatsD {
+            transport: Arc::new(Transport::Uds {
+                path: path.into(),
+                socket: Mutex::new(None),
+            }),
+            distributions,
+        }
+    }
+
+    /// Send `metrics`, packing as many lines as fit in each datagram
+    ///
+    /// Sends on the blocking thread pool, as a UDS send may wait for the agent to catch up.
+    pub(crate) async fn write(&self, metrics: &[DataDogMetric]) -> Result<()> {
+        let datagrams = datagrams(metrics, self.distributions, self.max_datagram());
+        if datagrams.is_empty() {
+            return Ok(());
+        }
+        let transport = self.transport.clone();
+        tokio::task::spawn_blocking(move || {
+            datagrams
+                .iter()
+                .try_for_each(|datagram| transport.send(datagram.as_bytes()))
+        })
+        .await??;
+        Ok(())
+    }
+
+    fn max_datagram(&self) -> usize {
+        match *self.transport {
+            Transport::Udp(_) => MAX_UDP_DATAGRAM,
+            #[cfg(unix)]
+            Transport::Uds { .. } => MAX_UDS_DATAGRAM,
+        }
+    }
+}
+
+impl Transport {
+    fn send(&self, datagram: &[u8]) -> io::Result<()> {
+        match self {
+            Transport::Udp(socket) => socket.send(datagram).map(|_| ()),
+            #[cfg(unix)]
+            Transport::Uds { path, socket } => {
+                let mut socket = socket.lock();
+                if let Some(connected) = socket.as_ref() {
+                    match connected.send(datagram) {
+                        Ok(_) => return Ok(()),
+                        Err(e) => debug!(error = ?e, "DogStatsD socket send failed, reconnecting"),
+                    }
+                }
+                *socket = None;
+                let connected = UnixDatagram::unbound()?;
+                connected.connect(path)?;
+                connected.set_write_timeout(Some(UDS_WRITE_TIMEOUT))?;
+                connected.send(datagram)?;
+                *socket = Some(connected);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Encode a point of `metric` as a DogStatsD line
+fn line(metric: &DataDogMetric, value: &DataDogMetricValue, distributions: bool) -> String {
+    let metric_type = match metric.metric_type {
+        DataDogMetricType::Count => "c",
+        DataDogMetricType::Gauge => "g",
+        DataDogMetricType::Histogram if distributions => "d",
+        DataDogMetricType::Histogram => "h",
+    };
+    let value = match value {
+        DataDogMetricValue::Float(value) => value.to_string(),
+        DataDogMetricValue::Unsigned(value) => value.to_string(),
+    };
+    let mut line = format!("{}:{value}|{metric_type}", metric.metric);
+    if !metric.tags.is_empty() {
+        line.push_str("|#");
+        line.push_str(&metric.tags.join(","));
+    }
+    line
+}
+
+/// Newline separated lines of `metrics`, grouped into datagrams of at most `max` bytes unless a
+/// single line is larger
+fn datagrams(metrics: &[DataDogMetric], distributions: bool, max: usize) -> Vec<String> {
+    let mut datagrams = vec![];
+    let mut datagram = String::new();
+    for metric in metrics {
+        for value in &metric.points {
+            let line = line(metric, value, distributions);
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > max {
+                datagrams.push(std::mem::take(&mut datagram));
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(&line);
+        }
+    }
+    if !datagram.is_empty() {
+        datagrams.push(datagram);
+    }
+    datagrams
+}
@@ -0,0 +1,46 @@
+//! Handling of flushes collecting no metrics
+
+use std::sync::Arc;
+
+/// Name of the gauge sent by [`DataDogEmptyFlush::Heartbeat`]
+pub const HEARTBEAT_METRIC: &str = "datadog.exporter.heartbeat";
+
+/// What a flush does when it collects no metrics
+///
+/// Tells "the app stopped recording metrics" apart from "the exporter is broken", which both
+/// look like missing data in DataDog when empty flushes are skipped
+#[derive(Clone, Default)]
+pub enum DataDogEmptyFlush {
+    /// Send nothing
+    #[default]
+    Skip,
+    /// Send a [`HEARTBEAT_METRIC`] gauge of 1 with the global tags
+    Heartbeat,
+    /// Log registry stats at debug
+    Log,
+    /// Call the callback with registry stats
+    Callback(Arc<dyn Fn(&DataDogRegistryStats) + Send + Sync>),
+}
+
+impl DataDogEmptyFlush {
+    /// [`DataDogEmptyFlush::Callback`] calling `callback`
+    pub fn callback<F>(callback: F) -> Self
+    where
+        F: Fn(&DataDogRegistryStats) + Send + Sync + 'static,
+    {
+        DataDogEmptyFlush::Callback(Arc::new(callback))
+    }
+}
+
+/// Registry state after a flush collecting no metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataDogRegistryStats {
+    /// Registered counters left for a later flush
+    pub counters: usize,
+    /// Registered gauges left for a later flush
+    pub gauges: usize,
+    /// Registered histograms left for a later flush
+    pub histograms: usize,
+    /// Number of empty flushes in a row, including this one
+    pub consecutive_empty_flushes: u64,
+}
@@ -2,242 +2,1729 @@
 
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use futures::future::try_join_all;
+use futures::future::{join_all, BoxFuture, FutureExt};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::Utc;
 use itertools::Itertools;
-use metrics::{Key, Label};
-use metrics_util::registry::{AtomicStorage, Registry};
-use reqwest::header::CONTENT_ENCODING;
-use reqwest::Client;
-use tokio::spawn;
-use tokio::task::JoinHandle;
-use tokio_schedule::{every, Job};
-use tracing::{debug, warn};
-
-use crate::builder::DataDogConfig;
-use crate::data::{DataDogApiPost, DataDogMetric, DataDogSeries};
+use metrics::Key;
+use metrics_util::registry::{GenerationalAtomicStorage, Registry};
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::{spawn_blocking, JoinHandle};
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::api_key::DataDogApiKeyProvider;
+use crate::backfill::{self, DataDogBackfillProgress};
+use crate::builder::{DataDogBuilder, DataDogConfig};
+use crate::checkpoint;
+use crate::chunk::{compress_series, CodecSelector};
+use crate::clock::ClockSkew;
+use crate::collector::Collector;
+use crate::concurrency::RequestSlots;
+use crate::data::{
+    DataDogApiResponse, DataDogApiVersion, DataDogDistribution, DataDogDistributionPost,
+    DataDogMetric, DataDogMetricType, DataDogSeries, DataDogSeriesTypePolicy,
+};
+use crate::destination::ApiDestination;
+use crate::dogstatsd::DogStatsD;
+use crate::empty::DataDogEmptyFlush;
+#[cfg(feature = "failpoints")]
+use crate::failpoints::{self, FailAction, Failpoint};
+use crate::idempotency::IdempotencyKeys;
+use crate::jitter::{random_fraction, DataDogJitter};
+use crate::json;
+use crate::latency::LatencyTracker;
+use crate::memory::MemoryBudget;
+use crate::metadata::{DataDogMetadata, DataDogMetadataUpdate, MetadataStore};
+use crate::negotiation::{DataDogContentEncoding, HostEncodings, GZIP_MAGIC, ZSTD_MAGIC};
+use crate::priority::SeriesBudget;
+use crate::recent::{DataDogFlushSnapshot, RecentFlushes};
+use crate::retry::DataDogRetryPolicy;
+use crate::routing::ApiKeyRouting;
+use crate::shadow::Shadow;
+use crate::signer::{self, DataDogRequestSigner};
+#[cfg(feature = "stdout")]
+use crate::sink::StdoutSink;
+use crate::sink::{DataDogPayload, DataDogSink};
+use crate::status::{
+    DataDogEndpointStatus, DataDogFlushErrors, DataDogFlushStats, DataDogStatus,
+    DeliveryReportCallback,
+};
+use crate::worker::Worker;
 use crate::{Error, Result};
 
 // Size constants from https://docs.datadoghq.com/api/latest/metrics/#submit-metrics
-const MAX_PAYLOAD_BYTES: usize = 3200000;
-const MAX_DECOMPRESSED_PAYLOAD: usize = 62914560;
+pub(crate) const MAX_PAYLOAD_BYTES: usize = 3200000;
+pub(crate) const MAX_DECOMPRESSED_PAYLOAD: usize = 62914560;
+/// Payloads below this size are sent uncompressed in high resolution mode, compressing them costs
+/// more latency than it saves
+const HIGH_RESOLUTION_GZIP_MIN_BYTES: usize = 16384;
+/// Idle time after which pooled connections are closed, reqwest's default
+const WARM_CONNECTION: Duration = Duration::from_secs(90);
+/// Shortest schedule, the timer panics on an empty interval
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+/// Delay before resubmitting after a 429 response without rate limit headers
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(10);
+/// Payloads kept while rate limited, the oldest are dropped beyond this
+const MAX_DEFERRED_PAYLOADS: usize = 256;
 
-fn metric_requests(metrics: Vec<DataDogMetric>, gzip: bool) -> Result<Vec<Vec<u8>>> {
-    let series = metrics
+/// How metrics are encoded into request payloads
+#[derive(Clone)]
+struct Encoding {
+    series_types: DataDogSeriesTypePolicy,
+    gzip: bool,
+    /// Payloads smaller than this are sent uncompressed
+    gzip_min_bytes: usize,
+    sender_shards: Option<usize>,
+    /// Seconds covered by each point, set on every series
+    interval: Option<i64>,
+    api_version: DataDogApiVersion,
+    /// Submit histograms as distributions
+    distributions: bool,
+    /// Host set on every series
+    host: Option<String>,
+    /// Picks the codec of each compressed payload
+    codecs: CodecSelector,
+    /// Encoding time per flush above which adaptive compression falls back to the cheapest codec
+    compression_budget: Option<Duration>,
+}
+
+#[cfg(feature = "bench")]
+pub(crate) fn metric_requests(
+    metrics: Vec<DataDogMetric>,
+    series_types: &DataDogSeriesTypePolicy,
+    gzip: bool,
+) -> Result<Vec<Vec<u8>>> {
+    series_requests(
+        &metric_series(&metrics, series_types, None, None),
+        gzip.then(CodecSelector::default),
+        0,
+        DataDogApiVersion::V1,
+        &mut 0,
+    )
+}
+
+fn metric_series<'a>(
+    metrics: impl IntoIterator<Item = &'a DataDogMetric>,
+    series_types: &DataDogSeriesTypePolicy,
+    interval: Option<i64>,
+    host: Option<&str>,
+) -> Vec<DataDogSeries> {
+    metrics
         .into_iter()
-        .flat_map(DataDogSeries::new)
-        .collect_vec();
-    if gzip {
-        split_and_compress_series(&series)
-    } else {
-        split_series(&series)
+        .flat_map(|m| DataDogSeries::from_metric(m, series_types))
+        .map(|series| DataDogSeries {
+            interval,
+            host: host.map(String::from),
+            ..series
+        })
+        .collect_vec()
+}
+
+/// Encode `series` into request payloads, compressed with codecs picked by `compression` if set
+fn series_requests(
+    series: &[DataDogSeries],
+    compression: Option<CodecSelector>,
+    gzip_min_bytes: usize,
+    version: DataDogApiVersion,
+    compression_fallbacks: &mut usize,
+) -> Result<Vec<Vec<u8>>> {
+    #[cfg(feature = "failpoints")]
+    match failpoints::action(Failpoint::Serialize) {
+        Some(FailAction::Delay(delay)) => std::thread::sleep(delay),
+        Some(action) => return Err(Error::Injected(action)),
+        None => {}
+    }
+
+    if compression.is_some() && gzip_min_bytes > 0 {
+        let body = json::series_body(series, version)?;
+        if body.len() < gzip_min_bytes {
+            return Ok(vec![body]);
+        }
+    }
+
+    match compression {
+        Some(codecs) => compress_or_split_series(series, version, codecs, compression_fallbacks),
+        None => split_series(series, version),
     }
 }
 
-fn split_series(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-    let body = serde_json::to_vec(&DataDogApiPost { series })?;
-    if body.len() < MAX_PAYLOAD_BYTES {
-        Ok(vec![body])
-    } else {
-        let (left, right) = series.split_at(series.len() / 2);
-        Ok(split_series(left)?
-            .into_iter()
-            .chain(split_series(right)?)
-            .collect_vec())
+/// Compress `series`, sending them uncompressed if the encoder fails so a transient failure
+/// doesn't drop the whole flush
+fn compress_or_split_series(
+    series: &[DataDogSeries],
+    version: DataDogApiVersion,
+    codecs: CodecSelector,
+    compression_fallbacks: &mut usize,
+) -> Result<Vec<Vec<u8>>> {
+    #[cfg(feature = "failpoints")]
+    let compressed = match failpoints::action(Failpoint::Compress) {
+        Some(FailAction::Delay(delay)) => {
+            std::thread::sleep(delay);
+            compress_series(series, version, codecs)
+        }
+        Some(action) => Err(std::io::Error::other(format!("injected {action:?}")).into()),
+        None => compress_series(series, version, codecs),
+    };
+    #[cfg(not(feature = "failpoints"))]
+    let compressed = compress_series(series, version, codecs);
+
+    match compressed {
+        Err(Error::IOError(e)) => {
+            warn!(error = ?e, series = series.len(), "Compression failed, sending uncompressed");
+            *compression_fallbacks += 1;
+            split_series(series, version)
+        }
+        result => result,
     }
 }
 
-fn split_and_compress_series(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-    fn split(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-        let (left, right) = series.split_at(series.len() / 2);
-        Ok(split_and_compress_series(left)?
-            .into_iter()
-            .chain(split_and_compress_series(right)?)
-            .collect_vec())
+fn distribution_requests(
+    distributions: &[DataDogDistribution],
+    gzip: bool,
+    compression_fallbacks: &mut usize,
+) -> Result<Vec<Vec<u8>>> {
+    let body = json::to_vec(&DataDogDistributionPost {
+        series: distributions,
+    })?;
+    let uncompressed = body.len();
+    let compressed = gzip.then(|| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&body)?;
+        encoder.finish()
+    });
+    let body = match compressed {
+        Some(Ok(compressed)) => compressed,
+        Some(Err(e)) => {
+            warn!(error = ?e, "Compression failed, sending uncompressed");
+            *compression_fallbacks += 1;
+            body
+        }
+        None => body,
+    };
+    if body.len() < MAX_PAYLOAD_BYTES && uncompressed <= MAX_DECOMPRESSED_PAYLOAD {
+        return Ok(vec![body]);
+    }
+
+    let (left, right) = match distributions {
+        [single] => {
+            let (left, right) = single.split_points().ok_or_else(|| Error::SeriesTooLarge {
+                metric: single.metric.clone(),
+                bytes: body.len(),
+            })?;
+            (vec![left], vec![right])
+        }
+        _ => {
+            let (left, right) = distributions.split_at(distributions.len() / 2);
+            (left.to_vec(), right.to_vec())
+        }
+    };
+    Ok(distribution_requests(&left, gzip, compression_fallbacks)?
+        .into_iter()
+        .chain(distribution_requests(&right, gzip, compression_fallbacks)?)
+        .collect_vec())
+}
+
+/// DataDog intake endpoint a payload is posted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Intake {
+    Series,
+    Distributions,
+}
+
+/// Request body and the intake it is posted to
+#[derive(Clone)]
+struct Payload {
+    intake: Intake,
+    body: Vec<u8>,
+}
+
+/// Payload held back while the DataDog API rate limits us, resubmitted by a later flush
+struct DeferredPayload {
+    /// API key overriding the default one
+    api_key: Option<String>,
+    payload: Payload,
+}
+
+/// Request payloads sent one after the other with the same API key
+struct RequestBatch {
+    /// API key overriding the default one
+    api_key: Option<String>,
+    intake: Intake,
+    requests: Vec<Vec<u8>>,
+}
+
+/// Series and distributions of a payload, owned so they can be encoded on another thread
+struct PayloadSeries {
+    /// API key overriding the default one
+    api_key: Option<String>,
+    distributions: Vec<DataDogDistribution>,
+    series: Vec<DataDogSeries>,
+}
+
+/// Series and distributions of `payloads`, routed to their API key if `routed`
+fn payload_series(
+    payloads: &[DataDogPayload],
+    encoding: &Encoding,
+    routed: bool,
+) -> Vec<PayloadSeries> {
+    payloads
+        .iter()
+        .map(|payload| {
+            let (distributions, metrics): (Vec<_>, Vec<_>) =
+                payload.metrics.iter().partition(|m| {
+                    encoding.distributions && m.metric_type == DataDogMetricType::Histogram
+                });
+            PayloadSeries {
+                api_key: payload.api_key.clone().filter(|_| routed),
+                distributions: distributions
+                    .into_iter()
+                    .map(DataDogDistribution::from_metric)
+                    .collect(),
+                series: metric_series(
+                    metrics,
+                    &encoding.series_types,
+                    encoding.interval,
+                    encoding.host.as_deref(),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Encode payloads into batches of request payloads, batches are sent concurrently
+fn request_batches(
+    payloads: Vec<PayloadSeries>,
+    encoding: &Encoding,
+) -> Result<(Vec<RequestBatch>, usize)> {
+    let (gzip, gzip_min_bytes, version) =
+        (encoding.gzip, encoding.gzip_min_bytes, encoding.api_version);
+    let compression = gzip.then_some(encoding.codecs);
+
+    let mut batches = Vec::new();
+    let mut fallbacks = 0;
+    for PayloadSeries {
+        api_key,
+        distributions,
+        series,
+    } in payloads
+    {
+        if !distributions.is_empty() {
+            batches.push(RequestBatch {
+                api_key: api_key.clone(),
+                intake: Intake::Distributions,
+                requests: check_payloads(
+                    distribution_requests(&distributions, gzip, &mut fallbacks)?,
+                    distributions.iter().map(|d| d.metric.as_str()),
+                )?,
+            });
+        }
+        if series.is_empty() {
+            continue;
+        }
+        match encoding.sender_shards {
+            Some(shards) => {
+                for shard in shard_series(series, shards) {
+                    batches.push(RequestBatch {
+                        api_key: api_key.clone(),
+                        intake: Intake::Series,
+                        requests: check_payloads(
+                            series_requests(
+                                &shard,
+                                compression,
+                                gzip_min_bytes,
+                                version,
+                                &mut fallbacks,
+                            )?,
+                            shard.iter().map(|s| s.metric.as_str()),
+                        )?,
+                    });
+                }
+            }
+            None => batches.extend(
+                check_payloads(
+                    series_requests(
+                        &series,
+                        compression,
+                        gzip_min_bytes,
+                        version,
+                        &mut fallbacks,
+                    )?,
+                    series.iter().map(|s| s.metric.as_str()),
+                )?
+                .into_iter()
+                .map(|request| RequestBatch {
+                    api_key: api_key.clone(),
+                    intake: Intake::Series,
+                    requests: vec![request],
+                }),
+            ),
+        }
+    }
+    Ok((batches, fallbacks))
+}
+
+/// Reject payloads exceeding the DataDog limits before sending them, instead of letting the API
+/// answer with an opaque 413
+fn check_payloads<'a>(
+    requests: Vec<Vec<u8>>,
+    metrics: impl Iterator<Item = &'a str>,
+) -> Result<Vec<Vec<u8>>> {
+    match requests.iter().map(Vec::len).max() {
+        Some(bytes) if bytes >= MAX_PAYLOAD_BYTES => Err(Error::PayloadTooLarge {
+            series: metrics.map(str::to_string).unique().collect(),
+            bytes,
+        }),
+        _ => Ok(requests),
+    }
+}
+
+/// Whether `error` is a 429 response, the payload should be resubmitted later
+fn is_rate_limited(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::RequestFailed { source, .. } if source.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    )
+}
+
+/// Delay before resubmitting after a 429 response, from `Retry-After` in seconds or as an HTTP
+/// date, or DataDog's `X-RateLimit-Reset` in seconds
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(value) = header(RETRY_AFTER.as_str()) {
+        if let Ok(seconds) = value.trim().parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(at) = chrono::DateTime::parse_from_rfc2822(value) {
+            return Some(
+                (at.with_timezone(&Utc) - Utc::now())
+                    .to_std()
+                    .unwrap_or_default(),
+            );
+        }
     }
+    header("x-ratelimit-reset")
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Partition series into `shards` groups, keeping series with the same name and tags together
+fn shard_series(series: Vec<DataDogSeries>, shards: usize) -> Vec<Vec<DataDogSeries>> {
+    let mut sharded = vec![Vec::new(); shards];
+    for s in series {
+        sharded[s.shard(shards)].push(s);
+    }
+    sharded.retain(|shard| !shard.is_empty());
+    sharded
+}
+
+pub(crate) fn split_series(
+    series: &[DataDogSeries],
+    version: DataDogApiVersion,
+) -> Result<Vec<Vec<u8>>> {
+    let body = json::series_body(series, version)?;
+    if body.len() < MAX_PAYLOAD_BYTES && body.len() <= MAX_DECOMPRESSED_PAYLOAD {
+        Ok(vec![body])
+    } else {
+        split(series, body.len(), |series| split_series(series, version))
+    }
+}
 
-    let body = serde_json::to_vec(&DataDogApiPost { series })?;
+pub(crate) fn split_and_compress_series(
+    series: &[DataDogSeries],
+    version: DataDogApiVersion,
+) -> Result<Vec<Vec<u8>>> {
+    let body = json::series_body(series, version)?;
+    let encode = |series: &[DataDogSeries]| split_and_compress_series(series, version);
 
     if body.len() > MAX_DECOMPRESSED_PAYLOAD {
-        return split(series);
+        return split(series, body.len(), encode);
     }
 
     let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(&serde_json::to_vec(&DataDogApiPost { series })?)?;
+    encoder.write_all(&body)?;
 
     let compressed = encoder.finish()?;
 
     if compressed.len() < MAX_PAYLOAD_BYTES {
         Ok(vec![compressed])
     } else {
-        split(series)
+        split(series, compressed.len(), encode)
+    }
+}
+
+/// Halve `series` and encode each side with `encode`
+///
+/// A lone series is split by its points instead, and rejected once it is down to a single point
+fn split<F>(series: &[DataDogSeries], bytes: usize, encode: F) -> Result<Vec<Vec<u8>>>
+where
+    F: Fn(&[DataDogSeries]) -> Result<Vec<Vec<u8>>>,
+{
+    if let [single] = series {
+        let (left, right) = single.split_points().ok_or_else(|| Error::SeriesTooLarge {
+            metric: single.metric.clone(),
+            bytes,
+        })?;
+        return Ok(encode(&[left])?
+            .into_iter()
+            .chain(encode(&[right])?)
+            .collect_vec());
+    }
+    let (left, right) = series.split_at(series.len() / 2);
+    Ok(encode(left)?
+        .into_iter()
+        .chain(encode(right)?)
+        .collect_vec())
+}
+
+/// Export `metrics` once with the exporter configured by `builder`, without a recorder or schedule
+///
+/// For short-lived jobs pushing a handful of values before exiting
+///
+/// ```no_run
+/// # async fn example() -> metrics_datadog_exporter::Result<()> {
+/// use metrics_datadog_exporter::{export_once, DataDogBuilder, DataDogMetric};
+///
+/// let metrics: Vec<DataDogMetric> = vec![];
+/// let builder = DataDogBuilder::default().write_to_api(true, Some("API_KEY".to_string()));
+/// export_once(builder, metrics).await
+/// # }
+/// ```
+pub async fn export_once(builder: DataDogBuilder, metrics: Vec<DataDogMetric>) -> Result<()> {
+    builder.build_exporter()?.export_metrics(metrics).await
+}
+
+/// Spawn `future` as a task named `name` on `runtime`, the current runtime if [`None`]
+///
+/// Task names need `--cfg tokio_unstable`, otherwise the task runs in a span named `name`
+fn spawn_named<F>(name: &'static str, runtime: Option<&Handle>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let current;
+    let runtime = match runtime {
+        Some(runtime) => runtime,
+        None => {
+            current = Handle::current();
+            &current
+        }
+    };
+    #[cfg(tokio_unstable)]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn_on(future, runtime)
+        .expect("Failed to spawn task");
+    #[cfg(not(tokio_unstable))]
+    runtime.spawn(future.instrument(info_span!("task", task = name)))
+}
+
+/// Clears the in-flight flag when a flush completes or is cancelled
+struct InFlight<'a>(&'a AtomicBool);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// The DataDog API as a sink of an exporter, keeping the encoding and sending stats of a flush
+struct ApiSink<'a> {
+    exporter: &'a DataDogExporter,
+    stats: Mutex<DataDogFlushStats>,
+}
+
+impl<'a> ApiSink<'a> {
+    fn new(exporter: &'a DataDogExporter) -> Self {
+        ApiSink {
+            exporter,
+            stats: Mutex::default(),
+        }
+    }
+
+    fn stats(self) -> DataDogFlushStats {
+        self.stats.into_inner()
+    }
+}
+
+impl DataDogSink for ApiSink<'_> {
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut stats = DataDogFlushStats::default();
+            let result = self.exporter.write_to_api(payloads, &mut stats).await;
+            *self.stats.lock() = stats;
+            result
+        }
+        .boxed()
     }
 }
 
 /// Metric exporter
 pub struct DataDogExporter {
-    registry: Arc<Registry<Key, AtomicStorage>>,
+    collector: Arc<Collector>,
+    #[cfg(feature = "stdout")]
     write_to_stdout: bool,
     write_to_api: bool,
-    api_host: String,
+    api_hosts: Vec<String>,
     api_client: Option<Client>,
-    api_key: Option<String>,
-    tags: Vec<Label>,
-    gzip: bool,
+    api_key: Option<Arc<dyn DataDogApiKeyProvider>>,
+    encoding: Encoding,
+    high_resolution: bool,
+    flushing: AtomicBool,
+    offload_blocking: bool,
+    app_key: Option<String>,
+    metadata_rate_limit: u32,
+    metadata: MetadataStore,
+    strict_intake: bool,
+    panic_on_intake_error: bool,
+    trace_headers: bool,
+    api_key_routing: Option<ApiKeyRouting>,
+    clock_skew: Option<ClockSkew>,
+    series_budget: Option<SeriesBudget>,
+    latency: Option<LatencyTracker>,
+    tag_resumed_flush: bool,
+    resumed: AtomicBool,
+    metadata_overrides: HashMap<String, DataDogMetadata>,
+    keepalive: Option<Duration>,
+    last_request: Mutex<Option<Instant>>,
+    idempotency: Option<IdempotencyKeys>,
+    worker: Option<Worker>,
+    empty_flush: DataDogEmptyFlush,
+    empty_flushes: AtomicU64,
+    shadow: Option<Shadow>,
+    stage_timings: bool,
+    /// Sinks written to alongside the API
+    sinks: Vec<Arc<dyn DataDogSink>>,
+    /// Other organizations submitted to alongside the API, without failing the flush
+    destinations: Vec<ApiDestination>,
+    recent_flushes: Option<RecentFlushes>,
+    metadata_sync_interval: Option<Duration>,
+    synced_metadata: Mutex<HashMap<String, DataDogMetadata>>,
+    retry: Option<DataDogRetryPolicy>,
+    rate_limited_until: Mutex<Option<Instant>>,
+    deferred: Mutex<VecDeque<DeferredPayload>>,
+    shutdown: watch::Sender<bool>,
+    scheduled: tokio::sync::Mutex<()>,
+    /// Flush requests sent to the scheduled task, replied to once flushed
+    triggers: mpsc::UnboundedSender<oneshot::Sender<Result<()>>>,
+    /// Receiving end of `triggers`, taken by [`DataDogExporter::schedule`]
+    pending_triggers: Mutex<Option<mpsc::UnboundedReceiver<oneshot::Sender<Result<()>>>>>,
+    shutdown_report: Option<DeliveryReportCallback>,
+    flush_jitter: Option<DataDogJitter>,
+    request_slots: Option<RequestSlots>,
+    request_signer: Option<Arc<dyn DataDogRequestSigner>>,
+    memory_budget: Option<MemoryBudget>,
+    /// Encodings each API host accepts
+    host_encodings: HostEncodings,
+    runtime: Option<Handle>,
+    checkpoint_on_crash: bool,
+    ready: watch::Sender<bool>,
+    status: Mutex<DataDogStatus>,
 }
 
 impl DataDogExporter {
     pub(crate) fn new(
-        registry: Arc<Registry<Key, AtomicStorage>>,
+        registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+        metadata: MetadataStore,
         client: Option<Client>,
         config: DataDogConfig,
-    ) -> Self {
-        DataDogExporter {
-            registry,
+    ) -> Result<Self> {
+        let worker = if config.compression_thread {
+            Some(Worker::new("dd-exporter-compress")?)
+        } else {
+            None
+        };
+        let collector = Arc::new(Collector::new(registry, metadata.clone(), &config));
+        if config.checkpoint_on_crash {
+            checkpoint::install(Arc::downgrade(&collector));
+        }
+        let dogstatsd = match &config.dogstatsd_socket {
+            _ if !config.write_to_dogstatsd => None,
+            #[cfg(unix)]
+            Some(path) => Some(DogStatsD::uds(path, config.distributions)),
+            _ => Some(DogStatsD::udp(
+                &config.dogstatsd_addr,
+                config.distributions,
+            )?),
+        };
+        let mut sinks: Vec<Arc<dyn DataDogSink>> = vec![];
+        #[cfg(feature = "stdout")]
+        if config.write_to_stdout {
+            sinks.push(Arc::new(StdoutSink));
+        }
+        if let Some(dogstatsd) = dogstatsd {
+            sinks.push(Arc::new(dogstatsd));
+        }
+        sinks.extend(config.sinks);
+        let api_hosts = std::iter::once(config.api_host)
+            .chain(config.fallback_api_hosts)
+            .collect_vec();
+        let host_encodings = HostEncodings::new(&api_hosts, &config.accepted_encodings);
+        let request_slots = config.request_concurrency.map(|(max_requests, weights)| {
+            RequestSlots::new(max_requests, &weights, api_hosts.len())
+        });
+        let status = DataDogStatus {
+            endpoints: api_hosts
+                .iter()
+                .enumerate()
+                .map(|(index, host)| DataDogEndpointStatus {
+                    concurrency_limit: request_slots.as_ref().and_then(|slots| slots.limit(index)),
+                    ..DataDogEndpointStatus::new(host)
+                })
+                .collect(),
+            ..DataDogStatus::default()
+        };
+        let (triggers, pending_triggers) = mpsc::unbounded_channel();
+        Ok(DataDogExporter {
+            collector,
+            #[cfg(feature = "stdout")]
             write_to_stdout: config.write_to_stdout,
             write_to_api: config.write_to_api,
-            api_host: config.api_host,
+            api_hosts,
             api_client: client,
             api_key: config.api_key,
-            tags: config.tags,
-            gzip: config.gzip,
-        }
+            encoding: Encoding {
+                series_types: config.series_types,
+                gzip: config.gzip,
+                gzip_min_bytes: if config.high_resolution {
+                    HIGH_RESOLUTION_GZIP_MIN_BYTES
+                } else {
+                    0
+                },
+                sender_shards: config.sender_shards,
+                interval: config.high_resolution.then_some(1),
+                api_version: config.api_version,
+                distributions: config.distributions,
+                host: config.host,
+                codecs: CodecSelector {
+                    adaptive: config.compression_budget.is_some(),
+                    over_budget: false,
+                    #[cfg(feature = "zstd")]
+                    zstd: config.api_version == DataDogApiVersion::V2,
+                },
+                compression_budget: config.compression_budget,
+            },
+            high_resolution: config.high_resolution,
+            flushing: AtomicBool::new(false),
+            offload_blocking: config.offload_blocking,
+            app_key: config.app_key,
+            metadata_rate_limit: config.metadata_rate_limit,
+            metadata,
+            strict_intake: config.strict_intake,
+            panic_on_intake_error: config.panic_on_intake_error,
+            trace_headers: config.trace_headers,
+            api_key_routing: config
+                .api_key_routing
+                .map(|(tag, keys)| ApiKeyRouting::new(tag, keys)),
+            clock_skew: config.clock_skew_tolerance.map(ClockSkew::new),
+            series_budget: (config.max_series.is_some() || !config.high_priority.is_empty())
+                .then(|| SeriesBudget::new(config.max_series, config.high_priority.clone())),
+            latency: config
+                .latency_slo
+                .map(|(slo, callback)| LatencyTracker::new(slo, callback)),
+            tag_resumed_flush: config.tag_resumed_flush,
+            resumed: AtomicBool::new(false),
+            metadata_overrides: config.metadata_overrides,
+            keepalive: config.keepalive,
+            last_request: Mutex::new(None),
+            idempotency: config.idempotency_window.map(IdempotencyKeys::new),
+            worker,
+            empty_flush: config.empty_flush,
+            empty_flushes: AtomicU64::new(0),
+            shadow: config
+                .shadow
+                .map(|(percent, callback)| Shadow::new(percent, callback)),
+            stage_timings: config.stage_timings,
+            sinks,
+            destinations: config.destinations,
+            recent_flushes: config.recent_flushes.map(RecentFlushes::new),
+            metadata_sync_interval: config.metadata_sync_interval,
+            synced_metadata: Mutex::default(),
+            retry: config.retry,
+            rate_limited_until: Mutex::default(),
+            deferred: Mutex::default(),
+            shutdown: watch::channel(false).0,
+            scheduled: tokio::sync::Mutex::default(),
+            triggers,
+            pending_triggers: Mutex::new(Some(pending_triggers)),
+            shutdown_report: config.shutdown_report,
+            flush_jitter: config.flush_jitter,
+            request_slots,
+            request_signer: config.request_signer,
+            memory_budget: config
+                .memory_budget
+                .map(|bytes| MemoryBudget::new(bytes, config.high_priority.clone())),
+            host_encodings,
+            runtime: config.runtime,
+            checkpoint_on_crash: config.checkpoint_on_crash,
+            ready: watch::channel(!config.write_to_api).0,
+            status: Mutex::new(status),
+        })
+    }
+
+    /// Current exporter status
+    pub fn status(&self) -> DataDogStatus {
+        let mut status = self.status.lock().clone();
+        status.discarded_samples = self.collector.discarded_samples();
+        status.destinations = self
+            .destinations
+            .iter()
+            .map(ApiDestination::status)
+            .collect();
+        status
     }
 
     /// Write metrics every [`Duration`]
+    ///
+    /// Intervals keep their full precision, e.g. 500 milliseconds, but points are timestamped in
+    /// seconds so gauges flushed twice within a second overwrite each other in DataDog. An empty
+    /// interval is raised to 1 millisecond.
+    ///
+    /// Flushes missed while the process was suspended are collapsed into a single flush. Use
+    /// [`DataDogExporter::trigger_flush`] on the returned exporter to flush outside the interval.
+    ///
+    /// The flush task runs on the runtime set with
+    /// [`DataDogBuilder::runtime`](crate::DataDogBuilder::runtime), the current one otherwise.
     pub fn schedule(self, interval: Duration) -> (Arc<Self>, JoinHandle<()>) {
+        let interval = interval.max(MIN_INTERVAL);
+        if self.high_resolution && interval != Duration::from_secs(1) {
+            warn!(
+                interval = ?interval,
+                "High resolution mode expects a 1 second interval, points will be mislabeled"
+            );
+        }
         let exporter = Arc::new(self);
         let scheduled_exporter = exporter.clone();
-        let every = every(interval.as_secs() as u32).seconds().perform(move || {
-            let exporter = scheduled_exporter.clone();
-            async move {
-                let result = exporter.clone().flush().await;
-                if let Err(e) = result {
+        let mut shutdown = exporter.shutdown.subscribe();
+        let mut triggers = exporter
+            .pending_triggers
+            .lock()
+            .take()
+            .expect("Exporter is scheduled once");
+        let runtime = exporter.runtime.clone();
+        let handle = spawn_named("dd-exporter-flush", runtime.as_ref(), async move {
+            // Held until the loop stops, so shutdown waits for an in-flight flush
+            let _scheduled = scheduled_exporter.scheduled.lock().await;
+            // Ticks come early by the jitter bound, each flush is then delayed by up to twice
+            // the bound, so flushes land anywhere within the bound of their nominal time
+            let jitter = scheduled_exporter
+                .flush_jitter
+                .map(|jitter| jitter.bound(interval))
+                .unwrap_or_default();
+            let start = tokio::time::Instant::now() + interval - jitter;
+            let mut ticks = tokio::time::interval_at(start, interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut last_tick = Utc::now();
+            let keepalive = scheduled_exporter
+                .keepalive
+                .map(|keepalive| keepalive.max(MIN_INTERVAL));
+            let mut probes = tokio::time::interval(keepalive.unwrap_or(interval));
+            probes.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let metadata_sync = scheduled_exporter
+                .metadata_sync_interval
+                .map(|interval| interval.max(MIN_INTERVAL));
+            let mut syncs = tokio::time::interval(metadata_sync.unwrap_or(interval));
+            syncs.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {}
+                    _ = shutdown.changed() => break,
+                    Some(reply) = triggers.recv() => {
+                        reply.send(scheduled_exporter.flush().await).ok();
+                        continue;
+                    }
+                    _ = probes.tick(), if keepalive.is_some() => {
+                        scheduled_exporter.probe().await;
+                        continue;
+                    }
+                    _ = syncs.tick(), if metadata_sync.is_some() => {
+                        if let Err(e) = scheduled_exporter.sync_metadata().await {
+                            warn!(error = ?e, "Failed to sync metric metadata");
+                        }
+                        continue;
+                    }
+                }
+                if !jitter.is_zero() {
+                    tokio::select! {
+                        _ = tokio::time::sleep(jitter.mul_f64(2.0 * random_fraction())) => {}
+                        _ = shutdown.changed() => break,
+                    }
+                }
+                // The monotonic clock may stop while suspended, the wall clock doesn't
+                let now = Utc::now();
+                let gap = (now - last_tick).to_std().unwrap_or_default();
+                last_tick = now;
+                if gap > interval * 2 {
+                    debug!(gap = ?gap, "Resumed after missing scheduled flushes");
+                    scheduled_exporter.resumed.store(true, Ordering::Relaxed);
+                }
+
+                if let Err(e) = scheduled_exporter.flush().await {
                     warn!(error = ?e, "Failed to flush metrics");
                 }
             }
         });
-        (exporter, spawn(every))
+        (exporter, handle)
+    }
+
+    /// Flush now, outside of the schedule
+    ///
+    /// Once scheduled, the flush runs on the scheduled task so it doesn't race a scheduled flush,
+    /// e.g. right before a batch job exits
+    pub async fn trigger_flush(&self) -> Result<()> {
+        if self.pending_triggers.lock().is_none() {
+            let (reply, flushed) = oneshot::channel();
+            if self.triggers.send(reply).is_ok() {
+                // Dropped without a reply if the scheduled task stopped in the meantime
+                if let Ok(result) = flushed.await {
+                    return result;
+                }
+            }
+        }
+        self.flush().await
+    }
+
+    /// Stop the scheduled flushes and flush the metrics recorded since the last one
+    ///
+    /// Resolves once a scheduled flush in flight and the final flush completed, i.e. the last
+    /// requests were answered. The delivery totals are then passed to the
+    /// [`DataDogBuilder::shutdown_report`] callback, or written to stdout as a line of JSON.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.shutdown.send_replace(true);
+        let _stopped = self.scheduled.lock().await;
+        let result = self.flush().await;
+
+        let report = self.status.lock().delivery.clone();
+        debug!(?report, "Metrics delivery report");
+        match &self.shutdown_report {
+            Some(callback) => callback(&report),
+            #[cfg(feature = "stdout")]
+            None if self.write_to_stdout => println!("{}", serde_json::to_string(&report)?),
+            None => {}
+        }
+        result
+    }
+
+    /// Current API key, resolved by the provider on every flush
+    async fn api_key(&self) -> Result<String> {
+        let api_key = self
+            .api_key
+            .as_ref()
+            .ok_or(Error::MissingApiKey)?
+            .api_key()
+            .await;
+        if api_key.is_err() {
+            self.status.lock().api_misconfigured = true;
+        }
+        api_key
+    }
+
+    /// Resolves once a request to the DataDog API succeeded, e.g. to mark a pod ready only once
+    /// its metrics are confirmed to be delivered
+    ///
+    /// Successful intake requests and [`keepalive`](crate::DataDogBuilder::keepalive) probes of
+    /// the API key validation endpoint count. Resolves immediately when not writing to the API.
+    pub async fn ready(&self) {
+        let mut ready = self.ready.subscribe();
+        while !*ready.borrow_and_update() {
+            // The sender lives as long as the exporter
+            if ready.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Request the API key validation endpoint if no request was made for the keepalive period
+    async fn probe(&self) {
+        let (Some(client), Some(keepalive)) = (&self.api_client, self.keepalive) else {
+            return;
+        };
+        if self
+            .last_request
+            .lock()
+            .is_some_and(|at| at.elapsed() < keepalive)
+        {
+            return;
+        }
+        let api_key = match self.api_key().await {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                debug!(error = ?e, "Skipping keepalive probe without an API key");
+                return;
+            }
+        };
+
+        let request = client
+            .get(format!("{}/validate", self.api_hosts[0]))
+            .header("DD-API-KEY", api_key);
+        let result = signer::send(client, request, self.request_signer.as_deref()).await;
+        match result {
+            Ok(response) => {
+                *self.last_request.lock() = Some(Instant::now());
+                if response.status().is_success() {
+                    self.ready.send_replace(true);
+                }
+                debug!(status = %response.status(), "Sent keepalive probe");
+            }
+            Err(e) => debug!(error = ?e, "Keepalive probe failed"),
+        }
     }
 
     /// Collect metrics
     ///
-    /// Note: This will clear histogram observations    
+    /// Note: This will clear histogram observations
     pub fn collect(&self) -> Vec<DataDogMetric> {
-        let counters = self
-            .registry
-            .get_counter_handles()
-            .into_iter()
-            .group_by(|(k, _)| k.clone())
-            .into_iter()
-            .map(|(key, values)| {
-                DataDogMetric::from_counter(
-                    key,
-                    values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
-                )
-            })
-            .collect_vec();
-
-        let gauges = self
-            .registry
-            .get_gauge_handles()
-            .into_iter()
-            .group_by(|(k, _)| k.clone())
-            .into_iter()
-            .map(|(key, values)| {
-                DataDogMetric::from_gauge(
-                    key,
-                    values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
-                )
-            })
-            .collect_vec();
+        self.collector.collect()
+    }
 
-        let histograms = self
-            .registry
-            .get_histogram_handles()
-            .into_iter()
-            .group_by(|(k, _)| k.clone())
-            .into_iter()
-            .map(|(key, values)| {
-                DataDogMetric::from_histogram(
-                    key,
-                    values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
-                )
-            })
-            .collect_vec();
+    /// Metrics written by the most recent flushes, oldest first
+    ///
+    /// Empty unless enabled with
+    /// [`DataDogBuilder::recent_flushes`](crate::DataDogBuilder::recent_flushes)
+    pub fn recent_flushes(&self) -> Vec<DataDogFlushSnapshot> {
+        self.recent_flushes
+            .as_ref()
+            .map(RecentFlushes::snapshots)
+            .unwrap_or_default()
+    }
 
-        self.registry.clear();
+    /// Discard every recorded metric without exporting it
+    ///
+    /// Handles obtained before the reset stay valid but are no longer collected
+    pub fn reset(&self) {
+        self.collector.reset();
+    }
 
-        counters
-            .into_iter()
-            .chain(gauges)
-            .chain(histograms)
-            .collect_vec()
+    /// Run `f` on the blocking thread pool if offloading is enabled, inline otherwise
+    async fn run_blocking<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if let Some(worker) = &self.worker {
+            worker.run(f).await
+        } else if self.offload_blocking {
+            let task = match &self.runtime {
+                Some(runtime) => runtime.spawn_blocking(f),
+                None => spawn_blocking(f),
+            };
+            Ok(task.await?)
+        } else {
+            Ok(f())
+        }
     }
 
     /// Flush metrics
     pub async fn flush(&self) -> Result<()> {
-        let metrics: Vec<DataDogMetric> = self.collect();
-        debug!("Flushing {} metrics", metrics.len());
+        self.flush_matching(|_| true).await
+    }
 
-        if self.write_to_stdout {
-            self.write_to_stdout(metrics.as_slice())?;
+    /// Flush metrics whose key matches `filter`, leaving the rest for the next flush
+    ///
+    /// ```no_run
+    /// # async fn example(exporter: metrics_datadog_exporter::DataDogExporter) {
+    /// exporter
+    ///     .flush_matching(|key| key.name().starts_with("batch."))
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn flush_matching<F>(&self, filter: F) -> Result<()>
+    where
+        F: Fn(&Key) -> bool + Send + 'static,
+    {
+        // High resolution flushes are dropped rather than queued behind a slow one
+        let _in_flight = if self.high_resolution {
+            if self.flushing.swap(true, Ordering::Acquire) {
+                debug!("Skipping flush, another one is in flight");
+                return Ok(());
+            }
+            Some(InFlight(&self.flushing))
+        } else {
+            None
+        };
+        let mut stats = DataDogFlushStats::default();
+        let result = self.export(filter, &mut stats).await;
+        self.record_flush(&result, stats);
+        result
+    }
+
+    async fn export<F>(&self, filter: F, stats: &mut DataDogFlushStats) -> Result<()>
+    where
+        F: Fn(&Key) -> bool + Send + 'static,
+    {
+        let start = Instant::now();
+        let collector = self.collector.clone();
+        let mut metrics = self
+            .run_blocking(move || collector.collect_matching(filter))
+            .instrument(info_span!("dd-exporter-collect"))
+            .await?;
+        stats.collect = start.elapsed();
+        if metrics.is_empty() {
+            self.on_empty_flush(&mut metrics);
+        } else {
+            self.empty_flushes.store(0, Ordering::Relaxed);
         }
+        self.write(metrics, stats).await
+    }
 
-        if self.write_to_api {
-            self.write_to_api(metrics).await?;
+    fn record_flush(&self, result: &Result<()>, stats: DataDogFlushStats) {
+        debug!(?stats, "Flush stages");
+        if self.stage_timings {
+            for (stage, duration) in [
+                ("datadog.exporter.flush.collect", stats.collect),
+                ("datadog.exporter.flush.transform", stats.transform),
+                ("datadog.exporter.flush.encode", stats.encode),
+                ("datadog.exporter.flush.send", stats.send),
+            ] {
+                self.collector
+                    .record_histogram(stage, duration.as_secs_f64());
+            }
         }
+        self.record_status(result, stats);
+    }
 
-        Ok(())
+    /// Keep the outcome of a flush in the status, also of flushes submitted as a destination
+    pub(crate) fn record_status(&self, result: &Result<()>, stats: DataDogFlushStats) {
+        let mut status = self.status.lock();
+        status.record_flush(result, &stats);
+        status.last_flush = Some(stats);
     }
 
-    fn write_to_stdout(&self, metrics: &[DataDogMetric]) -> Result<()> {
-        for metric in metrics {
-            for m in metric.to_metric_lines() {
-                println!("{}", serde_json::to_string(&m)?)
+    fn on_empty_flush(&self, metrics: &mut Vec<DataDogMetric>) {
+        let consecutive_empty_flushes = self.empty_flushes.fetch_add(1, Ordering::Relaxed) + 1;
+        match &self.empty_flush {
+            DataDogEmptyFlush::Skip => {}
+            DataDogEmptyFlush::Heartbeat => metrics.push(self.collector.heartbeat()),
+            DataDogEmptyFlush::Log => {
+                let stats = self.collector.stats(consecutive_empty_flushes);
+                debug!(?stats, "Flush collected no metrics");
+            }
+            DataDogEmptyFlush::Callback(callback) => {
+                callback(&self.collector.stats(consecutive_empty_flushes))
             }
         }
-        Ok(())
     }
 
-    async fn write_to_api(&self, metrics: Vec<DataDogMetric>) -> Result<(), Error> {
-        if metrics.is_empty() {
+    /// Export metrics recorded elsewhere, e.g. collected from a [`DataDogRecorder`] in another
+    /// process, instead of metrics from this exporter's registry
+    ///
+    /// [`DataDogRecorder`]: crate::DataDogRecorder
+    pub async fn export_metrics(&self, metrics: Vec<DataDogMetric>) -> Result<()> {
+        let mut stats = DataDogFlushStats::default();
+        let result = self.write(metrics, &mut stats).await;
+        self.record_flush(&result, stats);
+        result
+    }
+
+    /// Submit historical `metrics`, e.g. replayed after an outage, in batches of an hour of
+    /// timestamps, oldest first
+    ///
+    /// Each batch waits for a rate limit to reset, and skips the points DataDog would reject by
+    /// then: more than an hour old or more than 10 minutes in the future. `progress` is called
+    /// after each batch.
+    ///
+    /// ```no_run
+    /// # use metrics_datadog_exporter::{DataDogExporter, Result};
+    /// # async fn example(exporter: DataDogExporter) -> Result<()> {
+    /// # let replayed = vec![];
+    /// let progress = exporter
+    ///     .backfill(replayed, |progress| {
+    ///         println!("{}/{} batches", progress.batches, progress.total_batches)
+    ///     })
+    ///     .await?;
+    /// println!("Skipped {} points", progress.skipped_points);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn backfill(
+        &self,
+        metrics: Vec<DataDogMetric>,
+        mut progress: impl FnMut(DataDogBackfillProgress),
+    ) -> Result<DataDogBackfillProgress> {
+        let batches = backfill::batches(metrics);
+        let mut state = DataDogBackfillProgress {
+            total_batches: batches.len(),
+            ..DataDogBackfillProgress::default()
+        };
+        for mut batch in batches {
+            let rate_limited_until = *self.rate_limited_until.lock();
+            if let Some(until) = rate_limited_until {
+                tokio::time::sleep_until(until.into()).await;
+            }
+            state.skipped_points += backfill::retain_accepted(&mut batch, Utc::now().timestamp());
+            let points = batch.iter().map(|m| m.points.len()).sum::<usize>();
+            if !batch.is_empty() {
+                self.export_metrics(batch).await?;
+            }
+            state.batches += 1;
+            state.points += points;
+            progress(state);
+        }
+        Ok(state)
+    }
+
+    async fn write(
+        &self,
+        mut metrics: Vec<DataDogMetric>,
+        stats: &mut DataDogFlushStats,
+    ) -> Result<()> {
+        let start = Instant::now();
+        if self.resumed.swap(false, Ordering::Relaxed) && self.tag_resumed_flush {
+            for metric in &mut metrics {
+                metric.tags.push("resumed:true".to_string());
+            }
+        }
+        if let Some(clock_skew) = &self.clock_skew {
+            clock_skew.correct(&mut metrics);
+        }
+        if let Some(series_budget) = &self.series_budget {
+            let rate_limited = self.is_rate_limited() || !self.deferred.lock().is_empty();
+            let dropped = series_budget.shed(&mut metrics, rate_limited);
+            self.status.lock().dropped_series += dropped.values().sum::<usize>() as u64;
+        }
+        if let Some(memory_budget) = &self.memory_budget {
+            let mut deferred = self.deferred.lock();
+            let sizes = deferred
+                .iter()
+                .map(|deferred| deferred.payload.body.len())
+                .collect_vec();
+            let (pressure, shed_payloads, dropped) = memory_budget.shed(&mut metrics, &sizes);
+            deferred.drain(..shed_payloads);
+            drop(deferred);
+            self.collector
+                .record_gauge("datadog.exporter.memory.pressure", pressure.pressure);
+            let mut status = self.status.lock();
+            status.dropped_series += dropped.values().sum::<usize>() as u64;
+            status.memory = Some(pressure);
+        }
+        stats.transform = start.elapsed();
+        stats.metrics = metrics.len();
+        stats.points = metrics.iter().map(|m| m.points.len()).sum();
+        debug!("Flushing {} metrics", metrics.len());
+
+        if let Some(shadow) = &self.shadow {
+            shadow.write(&metrics);
+        }
+        if let Some(recent_flushes) = &self.recent_flushes {
+            recent_flushes.record(&metrics);
+        }
+        if self.checkpoint_on_crash {
+            checkpoint::update(&metrics);
+        }
+
+        let payloads = match &self.api_key_routing {
+            Some(routing) => routing.route(metrics),
+            None => vec![DataDogPayload {
+                api_key: None,
+                metrics,
+            }],
+        };
+
+        // Sinks are written concurrently, so a slow one doesn't delay the others
+        let api = ApiSink::new(self);
+        let sinks = self
+            .sinks
+            .iter()
+            .map(|sink| sink.as_ref())
+            .chain(self.write_to_api.then_some(&api as &dyn DataDogSink));
+        let (results, _) = futures::join!(
+            join_all(sinks.map(|sink| sink.submit(&payloads))),
+            join_all(
+                self.destinations
+                    .iter()
+                    .map(|destination| destination.submit(&payloads))
+            )
+        );
+        let api_stats = api.stats();
+        stats.encode = api_stats.encode;
+        stats.send = api_stats.send;
+        stats.payloads = api_stats.payloads;
+        stats.gzip_payloads = api_stats.gzip_payloads;
+        stats.zstd_payloads = api_stats.zstd_payloads;
+        stats.bytes = api_stats.bytes;
+
+        let mut error = None;
+        for e in results.into_iter().filter_map(Result::err) {
+            warn!(error = ?e, "Failed to write metrics to a sink");
+            error.get_or_insert(e);
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    pub(crate) async fn write_to_api(
+        &self,
+        payloads: &[DataDogPayload],
+        stats: &mut DataDogFlushStats,
+    ) -> Result<(), Error> {
+        let empty = payloads.iter().all(|payload| payload.metrics.is_empty());
+        if empty && self.deferred.lock().is_empty() {
+            return Ok(());
+        }
+
+        let client = self.api_client.as_ref().ok_or(Error::MissingApiClient)?;
+        let api_key = &self.api_key().await?;
+
+        let warm = self
+            .last_request
+            .lock()
+            .is_some_and(|at| at.elapsed() < WARM_CONNECTION);
+        if warm {
+            self.status.lock().warm_flushes += 1;
+        } else {
+            self.status.lock().cold_flushes += 1;
+        }
+
+        let start = Instant::now();
+        let mut encoding = self.encoding.clone();
+        if let Some(budget) = encoding.compression_budget {
+            let last_flush = self.status.lock().last_flush;
+            encoding.codecs.over_budget = last_flush.is_some_and(|stats| stats.encode > budget);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            encoding.codecs.zstd &= self.host_encodings.accepts(0, DataDogContentEncoding::Zstd);
+        }
+        // Payloads are routed to their API key by this exporter only, not by destinations
+        let payloads = payload_series(payloads, &encoding, self.api_key_routing.is_some());
+        let (mut batches, fallbacks) = self
+            .run_blocking(move || request_batches(payloads, &encoding))
+            .instrument(info_span!("dd-exporter-encode"))
+            .await??;
+        if !self.is_rate_limited() {
+            let deferred = std::mem::take(&mut *self.deferred.lock());
+            batches.extend(deferred.into_iter().map(|deferred| RequestBatch {
+                api_key: deferred.api_key,
+                intake: deferred.payload.intake,
+                requests: vec![deferred.payload.body],
+            }));
+        }
+        stats.encode = start.elapsed();
+        if fallbacks > 0 {
+            self.status.lock().compression_fallbacks += fallbacks as u64;
+        }
+        stats.payloads = batches.iter().map(|batch| batch.requests.len()).sum();
+        for request in batches.iter().flat_map(|batch| &batch.requests) {
+            if request.starts_with(&GZIP_MAGIC) {
+                stats.gzip_payloads += 1;
+            } else if request.starts_with(&ZSTD_MAGIC) {
+                stats.zstd_payloads += 1;
+            }
+        }
+
+        let start = Instant::now();
+
+        let results = join_all(batches.into_iter().map(|batch| {
+            async move {
+                let api_key = batch.api_key.as_deref().unwrap_or(api_key);
+                let mut results = Vec::with_capacity(batch.requests.len());
+                for request in batch.requests {
+                    let payload = Payload {
+                        intake: batch.intake,
+                        body: request,
+                    };
+                    if self.is_rate_limited() {
+                        self.defer(batch.api_key.clone(), payload);
+                        continue;
+                    }
+                    let idempotency_key = self
+                        .idempotency
+                        .as_ref()
+                        .map(|keys| keys.key(&payload.body));
+                    if let (Some(keys), Some(key)) = (&self.idempotency, &idempotency_key) {
+                        if keys.is_accepted(key) {
+                            debug!(idempotency_key = %key, "Skipping payload already accepted");
+                            continue;
+                        }
+                    }
+
+                    let result = self
+                        .send_with_retries(
+                            client,
+                            api_key,
+                            payload.clone(),
+                            idempotency_key.as_deref(),
+                        )
+                        .await;
+                    if result.as_ref().is_err_and(is_rate_limited) {
+                        self.defer(batch.api_key.clone(), payload);
+                        continue;
+                    }
+                    let result = result.and_then(|(request_id, status, message)| {
+                        self.check_intake(&request_id, status, &message)?;
+                        Ok((request_id, status, message))
+                    });
+                    if let (Ok(_), Some(keys), Some(key)) =
+                        (&result, &self.idempotency, idempotency_key)
+                    {
+                        keys.accept(key);
+                    }
+                    results.push(result.map(|response| (payload.body.len(), response)));
+                }
+                results
+            }
+            .instrument(info_span!("dd-exporter-sender"))
+        }))
+        .await;
+        stats.send = start.elapsed();
+        self.status.lock().deferred_payloads = self.deferred.lock().len();
+
+        let mut errors = DataDogFlushErrors::default();
+        for result in results.into_iter().flatten() {
+            errors.requests += 1;
+            match result {
+                Ok((bytes, (request_id, status, message))) => {
+                    stats.bytes += bytes;
+                    debug!(
+                        request_id = %request_id,
+                        status = %status,
+                        message = %message,
+                        "Response from DataDog API"
+                    )
+                }
+                Err(e) => errors.record(&e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::FlushFailed(errors))
+        }
+    }
+
+    fn check_intake(&self, request_id: &str, status: StatusCode, message: &str) -> Result<()> {
+        if !self.strict_intake {
             return Ok(());
         }
 
-        let requests = metric_requests(metrics, self.gzip)?;
+        let errors = serde_json::from_str::<DataDogApiResponse>(message)
+            .map(|response| response.errors)
+            .unwrap_or_default();
+        if status == StatusCode::ACCEPTED && errors.is_empty() {
+            return Ok(());
+        }
+
+        let error = Error::IntakeRejected {
+            request_id: request_id.to_string(),
+            status,
+            errors,
+        };
+        if self.panic_on_intake_error && cfg!(debug_assertions) {
+            panic!("{error}");
+        }
+        Err(error)
+    }
+
+    /// Units and descriptions of described metrics by name, including the ones set with
+    /// [`DataDogBuilder::describe`](crate::DataDogBuilder::describe)
+    pub fn metadata(&self) -> HashMap<String, DataDogMetadata> {
+        let mut metadata = self.metadata.read().clone();
+        metadata.extend(self.metadata_overrides.clone());
+        metadata
+    }
+
+    /// Push units and descriptions of described metrics to the DataDog metadata API
+    ///
+    /// Only metadata that changed since the last successful sync is pushed. Requests are paced by
+    /// [`DataDogBuilder::metadata_rate_limit`](crate::DataDogBuilder::metadata_rate_limit), see
+    /// [`DataDogBuilder::metadata_sync_interval`](crate::DataDogBuilder::metadata_sync_interval)
+    /// to sync periodically while scheduled.
+    /// Returns the number of metrics updated.
+    pub async fn sync_metadata(&self) -> Result<usize> {
+        let client = self.api_client.as_ref().ok_or(Error::MissingApiClient)?;
+        let api_key = self.api_key().await?;
+        let app_key = self.app_key.as_ref().ok_or(Error::MissingAppKey)?;
+
+        let changed = {
+            let synced = self.synced_metadata.lock();
+            self.metadata()
+                .into_iter()
+                .filter(|(name, metadata)| synced.get(name) != Some(metadata))
+                .collect_vec()
+        };
+        let mut pace = interval(Duration::from_secs(1) / self.metadata_rate_limit);
+        for (name, metadata) in &changed {
+            pace.tick().await;
+            let request = client
+                .put(format!(
+                    "{}/metrics/{}",
+                    self.api_hosts[0],
+                    self.collector.exported_name(name)
+                ))
+                .header("DD-API-KEY", &api_key)
+                .header("DD-APPLICATION-KEY", app_key)
+                .json(&DataDogMetadataUpdate::new(
+                    metadata,
+                    &self.encoding.series_types,
+                ));
+            let response = signer::send(client, request, self.request_signer.as_deref())
+                .await?
+                .error_for_status()?;
+            debug!(metric = %name, status = %response.status(), "Updated metric metadata");
+            self.synced_metadata
+                .lock()
+                .insert(name.clone(), metadata.clone());
+        }
+
+        Ok(changed.len())
+    }
+
+    /// Whether a 429 response asked to hold back requests until a later time
+    fn is_rate_limited(&self) -> bool {
+        self.rate_limited_until
+            .lock()
+            .is_some_and(|until| Instant::now() < until)
+    }
 
-        let responses = try_join_all(requests.into_iter().map(|request| async {
-            let mut request = self
-                .api_client
-                .as_ref()
-                .unwrap()
-                .post(format!("{}/series", self.api_host))
-                .header("DD-API-KEY", self.api_key.as_ref().unwrap())
-                .body(request);
+    /// Queue `payload` to be resubmitted once the rate limit resets
+    fn defer(&self, api_key: Option<String>, payload: Payload) {
+        let mut deferred = self.deferred.lock();
+        if deferred.len() == MAX_DEFERRED_PAYLOADS {
+            warn!("Too many payloads held back by the DataDog rate limit, dropping the oldest");
+            deferred.pop_front();
+        }
+        deferred.push_back(DeferredPayload { api_key, payload });
+    }
 
-            if self.gzip {
-                request = request.header(CONTENT_ENCODING, "gzip");
+    /// Send a payload, retrying transient failures following the retry policy
+    async fn send_with_retries(
+        &self,
+        client: &Client,
+        api_key: &str,
+        payload: Payload,
+        idempotency_key: Option<&str>,
+    ) -> Result<(String, StatusCode, String)> {
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .send(client, api_key, payload.clone(), idempotency_key)
+                .await;
+            match (&result, &self.retry) {
+                (Err(e), Some(retry)) if retry.retries(e, attempt) => {
+                    let delay = retry.delay(attempt);
+                    warn!(
+                        attempt,
+                        delay = ?delay,
+                        error = ?e,
+                        "Transient DataDog API failure, retrying"
+                    );
+                    self.status.lock().retries += 1;
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return result,
             }
+        }
+    }
 
-            let response = request.send().await?.error_for_status()?;
+    /// Post a payload to the API hosts in order, moving on to the next host while unreachable
+    ///
+    /// Returns the request ID shared by all attempts along with the response
+    async fn send(
+        &self,
+        client: &Client,
+        api_key: &str,
+        payload: Payload,
+        idempotency_key: Option<&str>,
+    ) -> Result<(String, StatusCode, String)> {
+        let id = Uuid::new_v4();
+
+        #[cfg(feature = "failpoints")]
+        match failpoints::action(Failpoint::Send) {
+            Some(FailAction::Delay(delay)) => tokio::time::sleep(delay).await,
+            Some(FailAction::Status(status)) => {
+                if let Ok(status) = StatusCode::from_u16(status) {
+                    if status.is_success() {
+                        return Ok((id.to_string(), status, String::new()));
+                    }
+                }
+                return Err(Error::Injected(FailAction::Status(status)));
+            }
+            Some(action) => return Err(Error::Injected(action)),
+            None => {}
+        }
+
+        let mut result = self
+            .post_negotiated(client, api_key, &id, 0, &payload, idempotency_key)
+            .await?;
+        for index in 1..self.api_hosts.len() {
+            match &result {
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    warn!(
+                        request_id = %id,
+                        host = %self.api_hosts[index - 1],
+                        error = ?e,
+                        "DataDog API host unreachable, failing over to {}",
+                        self.api_hosts[index]
+                    );
+                }
+                _ => break,
+            }
+            result = self
+                .post_negotiated(client, api_key, &id, index, &payload, idempotency_key)
+                .await?;
+        }
+
+        let request_id = id.to_string();
+        match result {
+            Ok((status, message)) => Ok((request_id, status, message)),
+            Err(source) => Err(Error::RequestFailed { request_id, source }),
+        }
+    }
+
+    /// Post a payload to `host` in an encoding it accepts
+    ///
+    /// A host rejecting a compressed payload with 415 Unsupported Media Type is sent it again
+    /// once in another encoding, which is used for this host from then on. Fails without posting
+    /// if the payload can't be re-encoded for the host.
+    async fn post_negotiated(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &Uuid,
+        host: usize,
+        payload: &Payload,
+        idempotency_key: Option<&str>,
+    ) -> Result<Result<(StatusCode, String), reqwest::Error>> {
+        // Only the v2 series intake accepts zstd
+        let zstd = cfg!(feature = "zstd")
+            && payload.intake == Intake::Series
+            && self.encoding.api_version == DataDogApiVersion::V2;
+        let payload = Payload {
+            intake: payload.intake,
+            body: self
+                .host_encodings
+                .negotiate(host, payload.body.clone(), zstd)?,
+        };
+        let encoding = DataDogContentEncoding::of(&payload.body);
+        let result = self
+            .post(client, api_key, id, host, payload.clone(), idempotency_key)
+            .await;
+        match &result {
+            Err(e)
+                if e.status() == Some(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+                    && encoding != DataDogContentEncoding::Identity =>
+            {
+                warn!(
+                    request_id = %id,
+                    host = %self.api_hosts[host],
+                    ?encoding,
+                    "DataDog API host rejected the payload encoding, re-encoding"
+                );
+                self.host_encodings.reject(host, encoding);
+                let payload = Payload {
+                    intake: payload.intake,
+                    body: self.host_encodings.negotiate(host, payload.body, zstd)?,
+                };
+                Ok(self
+                    .post(client, api_key, id, host, payload, idempotency_key)
+                    .await)
+            }
+            _ => Ok(result),
+        }
+    }
+
+    /// URL payloads for `intake` are posted to on `host`
+    fn intake_url(&self, host: &str, intake: Intake) -> String {
+        if intake == Intake::Distributions {
+            return format!("{host}/distribution_points");
+        }
+        match self.encoding.api_version {
+            DataDogApiVersion::V1 => format!("{host}/series"),
+            // API hosts are configured with the v1 base, e.g. `https://api.datadoghq.com/api/v1`
+            DataDogApiVersion::V2 => match host.strip_suffix("/v1") {
+                Some(base) => format!("{base}/v2/series"),
+                None => format!("{host}/series"),
+            },
+        }
+    }
+
+    async fn post(
+        &self,
+        client: &Client,
+        api_key: &str,
+        id: &Uuid,
+        host: usize,
+        payload: Payload,
+        idempotency_key: Option<&str>,
+    ) -> Result<(StatusCode, String), reqwest::Error> {
+        let Payload { intake, body } = payload;
+        let start = Instant::now();
+        let _slot = match &self.request_slots {
+            Some(slots) => slots.acquire(host).await,
+            None => None,
+        };
+        let slot_wait = start.elapsed();
+        let start = Instant::now();
+        // Small payloads may be sent uncompressed even with compression enabled
+        let encoding = DataDogContentEncoding::of(&body).header();
+        let result = async {
+            let mut request = client
+                .post(self.intake_url(&self.api_hosts[host], intake))
+                .header("DD-API-KEY", api_key)
+                .header("x-request-id", id.to_string())
+                .body(body);
+
+            if self.trace_headers {
+                let (trace_id, parent_id) = id.as_u64_pair();
+                request = request
+                    .header("x-datadog-trace-id", trace_id.to_string())
+                    .header("x-datadog-parent-id", parent_id.to_string());
+            }
+
+            if let Some(encoding) = encoding {
+                request = request.header(CONTENT_ENCODING, encoding);
+            }
+
+            if let Some(idempotency_key) = idempotency_key {
+                request = request.header("Idempotency-Key", idempotency_key);
+            }
+
+            let response = signer::send(client, request, self.request_signer.as_deref()).await?;
+            if let Some(clock_skew) = &self.clock_skew {
+                clock_skew.observe(response.headers());
+            }
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                let delay = retry_after(response.headers()).unwrap_or(DEFAULT_RATE_LIMIT_DELAY);
+                warn!(request_id = %id, delay = ?delay, "Rate limited by the DataDog API");
+                *self.rate_limited_until.lock() = Some(Instant::now() + delay);
+            }
+            let response = response.error_for_status()?;
             let status = response.status();
             let message = response.text().await?;
 
             Ok::<_, reqwest::Error>((status, message))
-        }))
-        .await?;
-
-        responses.into_iter().for_each(|(status, message)| {
-            debug!(status = %status, message = %message, "Response from DataDog API")
-        });
+        }
+        .await;
 
-        Ok(())
+        if let Some(latency) = &self.latency {
+            latency.record(start.elapsed());
+        }
+        if result.is_ok() {
+            *self.last_request.lock() = Some(Instant::now());
+            self.ready.send_replace(true);
+        }
+        let mut status = self.status.lock();
+        match &result {
+            Ok(_) => status.api_misconfigured = false,
+            Err(e) if e.status() == Some(StatusCode::FORBIDDEN) => status.api_misconfigured = true,
+            Err(_) => {}
+        }
+        if let Some(endpoint) = status.endpoints.get_mut(host) {
+            endpoint.record_request(&result, slot_wait);
+        }
+        result
     }
 }
@@ -0,0 +1,67 @@
+//! Error injection for resilience testing
+//!
+//! Lets applications exercise how they cope with a misbehaving metrics pipeline without standing
+//! up a flaky proxy. Failpoints are global and stay active until removed.
+//!
+//! ```
+//! use metrics_datadog_exporter::failpoints::{self, FailAction, Failpoint};
+//!
+//! failpoints::set(Failpoint::Send, FailAction::Status(500));
+//! // ... flush and observe the failure ...
+//! failpoints::clear();
+//! ```
+
+use std::time::Duration;
+
+use parking_lot::{const_mutex, Mutex};
+
+/// Stage of the export pipeline to inject a failure into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failpoint {
+    /// Serializing series into request payloads
+    Serialize,
+    /// Compressing request payloads, failures fall back to uncompressed payloads
+    Compress,
+    /// Sending a request payload to the DataDog API
+    Send,
+}
+
+/// Failure to inject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailAction {
+    /// Fail with an injected error
+    Error,
+    /// Respond with the given status without sending the request, failing unless it is a success
+    Status(u16),
+    /// Fail as if the request timed out
+    Timeout,
+    /// Wait before carrying on as usual
+    Delay(Duration),
+}
+
+static FAILPOINTS: Mutex<Vec<(Failpoint, FailAction)>> = const_mutex(Vec::new());
+
+/// Inject `action` at `failpoint`, replacing any action set before
+pub fn set(failpoint: Failpoint, action: FailAction) {
+    let mut failpoints = FAILPOINTS.lock();
+    failpoints.retain(|(f, _)| *f != failpoint);
+    failpoints.push((failpoint, action));
+}
+
+/// Stop injecting failures at `failpoint`
+pub fn remove(failpoint: Failpoint) {
+    FAILPOINTS.lock().retain(|(f, _)| *f != failpoint);
+}
+
+/// Stop injecting failures anywhere
+pub fn clear() {
+    FAILPOINTS.lock().clear();
+}
+
+pub(crate) fn action(failpoint: Failpoint) -> Option<FailAction> {
+    FAILPOINTS
+        .lock()
+        .iter()
+        .find(|(f, _)| *f == failpoint)
+        .map(|(_, action)| action.clone())
+}
@@ -0,0 +1,188 @@
+//! Sink writing metrics to rotated JSON lines files
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::{BoxFuture, FutureExt};
+use parking_lot::Mutex;
+
+use crate::data::DataDogMetric;
+use crate::sink::{DataDogPayload, DataDogSink};
+use crate::Result;
+
+/// Writes metrics to a file in DataDog JSON format, one line per point as written to stdout, for a
+/// forwarder to ship later, e.g. from air-gapped environments
+///
+/// Once the file reaches [`max_bytes`](Self::max_bytes) or [`max_age`](Self::max_age), it is
+/// renamed with the UTC time of the rotation appended, e.g. `metrics.jsonl.20240101T120000.000Z`,
+/// and a new file is started. Files are never rotated by default.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use metrics_datadog_exporter::{DataDogBuilder, DataDogFileSink};
+/// let builder = DataDogBuilder::default().sink(
+///     DataDogFileSink::new("/var/spool/metrics/metrics.jsonl")
+///         .max_bytes(64 * 1024 * 1024)
+///         .max_age(Duration::from_secs(3600))
+///         .gzip_rotated(true),
+/// );
+/// ```
+pub struct DataDogFileSink {
+    rotation: Rotation,
+    file: Arc<Mutex<Option<OpenFile>>>,
+}
+
+#[derive(Clone)]
+struct Rotation {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    gzip: bool,
+}
+
+struct OpenFile {
+    file: File,
+    bytes: u64,
+    opened: Instant,
+}
+
+impl DataDogFileSink {
+    /// Append metrics to the file at `path`, created if missing
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        DataDogFileSink {
+            rotation: Rotation {
+                path: path.into(),
+                max_bytes: None,
+                max_age: None,
+                gzip: false,
+            },
+            file: Arc::default(),
+        }
+    }
+
+    /// Rotate the file before it exceeds `bytes`, flushes larger than this get a file of their own
+    #[must_use]
+    pub fn max_bytes(self, bytes: u64) -> Self {
+        DataDogFileSink {
+            rotation: Rotation {
+                max_bytes: Some(bytes),
+                ..self.rotation
+            },
+            ..self
+        }
+    }
+
+    /// Rotate the file once `age` has passed since it was opened
+    #[must_use]
+    pub fn max_age(self, age: Duration) -> Self {
+        DataDogFileSink {
+            rotation: Rotation {
+                max_age: Some(age),
+                ..self.rotation
+            },
+            ..self
+        }
+    }
+
+    /// Compress rotated files with gzip, adding a `.gz` extension
+    #[must_use]
+    pub fn gzip_rotated(self, gzip: bool) -> Self {
+        DataDogFileSink {
+            rotation: Rotation {
+                gzip,
+                ..self.rotation
+            },
+            ..self
+        }
+    }
+}
+
+impl DataDogSink for DataDogFileSink {
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+        let rotation = self.rotation.clone();
+        let file = self.file.clone();
+        async move {
+            let mut lines = vec![];
+            for line in DataDogPayload::metrics(payloads).flat_map(DataDogMetric::to_metric_lines) {
+                serde_json::to_writer(&mut lines, &line)?;
+                lines.push(b'\n');
+            }
+            if lines.is_empty() {
+                return Ok(());
+            }
+            tokio::task::spawn_blocking(move || rotation.write(&mut file.lock(), &lines)).await??;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl Rotation {
+    fn write(&self, file: &mut Option<OpenFile>, lines: &[u8]) -> io::Result<()> {
+        if let Some(open) = file {
+            let full = self
+                .max_bytes
+                .is_some_and(|max| open.bytes > 0 && open.bytes + lines.len() as u64 > max);
+            let expired = self.max_age.is_some_and(|age| open.opened.elapsed() >= age);
+            if full || expired {
+                *file = None;
+                self.rotate()?;
+            }
+        }
+        let open = match file {
+            Some(open) => open,
+            None => file.insert(self.open()?),
+        };
+        open.file.write_all(lines)?;
+        open.bytes += lines.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> io::Result<OpenFile> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        Ok(OpenFile {
+            bytes: file.metadata()?.len(),
+            file,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Move the current file aside, compressing it if enabled
+    fn rotate(&self) -> io::Result<()> {
+        let mut base = self.path.clone().into_os_string();
+        base.push(Utc::now().format(".%Y%m%dT%H%M%S%.3fZ").to_string());
+        let mut rotated = PathBuf::from(&base);
+        // Rotations within the same millisecond
+        let mut sequence = 1;
+        while rotated.exists() || gzipped(&rotated).exists() {
+            let mut next = base.clone();
+            next.push(format!("-{sequence}"));
+            rotated = PathBuf::from(next);
+            sequence += 1;
+        }
+        fs::rename(&self.path, &rotated)?;
+        if self.gzip {
+            let mut encoder =
+                GzEncoder::new(File::create(gzipped(&rotated))?, Compression::default());
+            io::copy(&mut BufReader::new(File::open(&rotated)?), &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+            fs::remove_file(&rotated)?;
+        }
+        Ok(())
+    }
+}
+
+fn gzipped(path: &Path) -> PathBuf {
+    let mut gzipped = path.to_path_buf().into_os_string();
+    gzipped.push(".gz");
+    PathBuf::from(gzipped)
+}
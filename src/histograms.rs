@@ -0,0 +1,80 @@
+//! Stores of the histograms recorded outside the registry, shared by the recorder and the
+//! exporter
+
+#[cfg(feature = "recorder")]
+use metrics::{Histogram, Key};
+
+#[cfg(feature = "recorder")]
+use crate::reservoir::ReservoirStore;
+#[cfg(feature = "recorder")]
+use crate::sketch::SketchStore;
+
+/// Sketches or reservoirs histograms are recorded into, always empty without the recorder
+#[derive(Clone, Default)]
+pub(crate) struct HistogramStores {
+    #[cfg(feature = "recorder")]
+    pub(crate) sketches: Option<SketchStore>,
+    #[cfg(feature = "recorder")]
+    pub(crate) reservoirs: Option<ReservoirStore>,
+}
+
+impl HistogramStores {
+    /// Sketches within `relative_accuracy` if set, otherwise reservoirs of at most
+    /// `sample_limit` observations if set
+    #[cfg(feature = "recorder")]
+    pub(crate) fn new(relative_accuracy: Option<f64>, sample_limit: Option<usize>) -> Self {
+        let sketches = relative_accuracy.map(SketchStore::new);
+        let reservoirs = match sketches {
+            Some(_) => None,
+            None => sample_limit.map(ReservoirStore::new),
+        };
+        HistogramStores {
+            sketches,
+            reservoirs,
+        }
+    }
+
+    /// Handle of the histogram `key` in the store, [`None`] to record it in the registry
+    #[cfg(feature = "recorder")]
+    pub(crate) fn histogram(&self, key: &Key) -> Option<Histogram> {
+        if let Some(sketches) = &self.sketches {
+            return Some(sketches.histogram(key));
+        }
+        self.reservoirs
+            .as_ref()
+            .map(|reservoirs| reservoirs.histogram(key))
+    }
+
+    /// Histograms held until the next collection
+    pub(crate) fn len(&self) -> usize {
+        #[cfg(feature = "recorder")]
+        return self.sketches.as_ref().map_or(0, SketchStore::len)
+            + self.reservoirs.as_ref().map_or(0, ReservoirStore::len);
+        #[cfg(not(feature = "recorder"))]
+        0
+    }
+
+    /// Observations discarded by full reservoirs
+    pub(crate) fn discarded(&self) -> u64 {
+        #[cfg(feature = "recorder")]
+        return self
+            .reservoirs
+            .as_ref()
+            .map_or(0, ReservoirStore::discarded);
+        #[cfg(not(feature = "recorder"))]
+        0
+    }
+
+    /// Drop every histogram held
+    pub(crate) fn clear(&self) {
+        #[cfg(feature = "recorder")]
+        {
+            if let Some(sketches) = &self.sketches {
+                sketches.clear();
+            }
+            if let Some(reservoirs) = &self.reservoirs {
+                reservoirs.clear();
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Idempotency keys of payloads, so resubmitting an accepted payload doesn't count its points twice
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use chrono::Utc;
+use parking_lot::Mutex;
+
+/// Keys payloads by their content and time window, remembering the ones DataDog accepted
+///
+/// A payload identical to one accepted in the same window is a retry that raced with a slow
+/// response, sending it again would count its points twice
+pub(crate) struct IdempotencyKeys {
+    window: i64,
+    /// Window of each accepted key
+    accepted: Mutex<HashMap<String, i64>>,
+}
+
+impl IdempotencyKeys {
+    pub(crate) fn new(window: Duration) -> Self {
+        IdempotencyKeys {
+            window: (window.as_secs() as i64).max(1),
+            accepted: Mutex::default(),
+        }
+    }
+
+    fn current_window(&self) -> i64 {
+        Utc::now().timestamp() / self.window
+    }
+
+    /// Key of `body` in the current window
+    pub(crate) fn key(&self, body: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        self.current_window().hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    pub(crate) fn is_accepted(&self, key: &str) -> bool {
+        self.accepted.lock().contains_key(key)
+    }
+
+    pub(crate) fn accept(&self, key: String) {
+        let window = self.current_window();
+        let mut accepted = self.accepted.lock();
+        // Keys of past windows can't match anymore
+        accepted.retain(|_, accepted_window| *accepted_window >= window - 1);
+        accepted.insert(key, window);
+    }
+}
@@ -0,0 +1,113 @@
+//! Expiry of metrics kept across flushes
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use metrics::Key;
+use metrics_util::registry::{Generation, Generational, GenerationalAtomicStorage, Registry};
+use parking_lot::Mutex;
+
+/// Key, generation and when the generation last changed of each metric, by key hash as keys hold
+/// atomics
+type Activity = HashMap<u64, (Key, Generation, Instant)>;
+
+/// Finds counters and gauges left untouched for a while, from the generation of their handles
+/// incremented by every update, as `metrics_util::registry::Recency`
+pub(crate) struct IdleExpiry {
+    timeout: Duration,
+    counters: Mutex<Activity>,
+    gauges: Mutex<Activity>,
+}
+
+impl IdleExpiry {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        IdleExpiry {
+            timeout,
+            counters: Mutex::default(),
+            gauges: Mutex::default(),
+        }
+    }
+
+    /// Delete the counters matching `filter` left untouched for the timeout
+    pub(crate) fn expire_counters(
+        &self,
+        registry: &Registry<Key, GenerationalAtomicStorage>,
+        filter: &impl Fn(&Key) -> bool,
+    ) {
+        let mut activity = self.counters.lock();
+        let now = Instant::now();
+        let mut present = HashSet::new();
+        registry.retain_counters(|key, handle| {
+            !filter(key) || !self.expired(&mut activity, &mut present, key, handle, now)
+        });
+        Self::forget_absent(&mut activity, &present, filter);
+    }
+
+    /// Delete the gauges matching `filter` left untouched for the timeout
+    pub(crate) fn expire_gauges(
+        &self,
+        registry: &Registry<Key, GenerationalAtomicStorage>,
+        filter: &impl Fn(&Key) -> bool,
+    ) {
+        let mut activity = self.gauges.lock();
+        let now = Instant::now();
+        let mut present = HashSet::new();
+        registry.retain_gauges(|key, handle| {
+            !filter(key) || !self.expired(&mut activity, &mut present, key, handle, now)
+        });
+        Self::forget_absent(&mut activity, &present, filter);
+    }
+
+    /// Whether the metric of `handle` was left untouched for the timeout
+    ///
+    /// Called with the registry shard locked, so the handle can't be updated or handed out before
+    /// it is deleted. Metrics the application still holds handles to never expire, what it records
+    /// through them after the deletion would be lost.
+    fn expired<T>(
+        &self,
+        activity: &mut Activity,
+        present: &mut HashSet<u64>,
+        key: &Key,
+        handle: &Generational<Arc<T>>,
+        now: Instant,
+    ) -> bool {
+        let hash = key.get_hash();
+        let generation = handle.get_generation();
+        match activity.get_mut(&hash) {
+            Some((k, last, touched)) if k == key => {
+                if *last != generation {
+                    *last = generation;
+                    *touched = now;
+                } else if now.duration_since(*touched) >= self.timeout
+                    && Arc::strong_count(handle.get_inner()) == 1
+                {
+                    activity.remove(&hash);
+                    return true;
+                }
+            }
+            // On a hash collision the first key is tracked, the other one never expires
+            Some(_) => return false,
+            None => {
+                activity.insert(hash, (key.clone(), generation, now));
+            }
+        }
+        present.insert(hash);
+        false
+    }
+
+    /// Forget the metrics matching `filter` deleted from the registry since the last expiry
+    fn forget_absent(
+        activity: &mut Activity,
+        present: &HashSet<u64>,
+        filter: &impl Fn(&Key) -> bool,
+    ) {
+        activity.retain(|hash, (key, _, _)| !filter(key) || present.contains(hash));
+    }
+
+    /// Forget every metric, e.g. after the registry is cleared
+    pub(crate) fn clear(&self) {
+        self.counters.lock().clear();
+        self.gauges.lock().clear();
+    }
+}
@@ -0,0 +1,37 @@
+//! Randomized delays
+
+use std::time::Duration;
+
+use uuid::Uuid;
+
+/// Random offset applied to each scheduled flush, so instances started together don't flush in
+/// lockstep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataDogJitter {
+    /// Flushes are moved by up to this fraction of the interval, earlier or later, e.g. `0.1`
+    /// for ±10%
+    Fraction(f64),
+    /// Flushes are moved by up to this duration, earlier or later
+    Range(Duration),
+}
+
+impl DataDogJitter {
+    /// Largest offset of a flush scheduled every `interval`, at most half the interval so flushes
+    /// keep their order, none for a fraction that isn't finite
+    pub(crate) fn bound(&self, interval: Duration) -> Duration {
+        let bound = match self {
+            DataDogJitter::Fraction(fraction) if fraction.is_finite() => {
+                interval.mul_f64(fraction.clamp(0.0, 1.0))
+            }
+            DataDogJitter::Fraction(_) => Duration::ZERO,
+            DataDogJitter::Range(range) => *range,
+        };
+        bound.min(interval / 2)
+    }
+}
+
+/// Uniformly distributed between 0 and 1
+pub(crate) fn random_fraction() -> f64 {
+    // The leading 48 bits of a v4 UUID are random, the version and variant bits come later
+    (Uuid::new_v4().as_u128() >> 80) as f64 / (1u64 << 48) as f64
+}
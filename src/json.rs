@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+use crate::data::{
+    DataDogApiPost, DataDogApiPostV2, DataDogApiVersion, DataDogSeries, DataDogSeriesV2,
+};
+use crate::Result;
+
+/// Serialize `value` with serde_json
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(value)?)
+}
+
+/// Serialize `value` with simd-json
+#[cfg(feature = "simd-json")]
+pub(crate) fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    Ok(simd_json::serde::to_vec(value)?)
+}
+
+/// Serialize a request body holding `series` in the payload shape of `version`
+pub(crate) fn series_body(series: &[DataDogSeries], version: DataDogApiVersion) -> Result<Vec<u8>> {
+    match version {
+        DataDogApiVersion::V1 => to_vec(&DataDogApiPost { series }),
+        DataDogApiVersion::V2 => to_vec(&DataDogApiPostV2 {
+            series: series.iter().map(DataDogSeriesV2::from).collect(),
+        }),
+    }
+}
+
+/// Serialize a single series in the payload shape of `version`
+pub(crate) fn series(series: &DataDogSeries, version: DataDogApiVersion) -> Result<Vec<u8>> {
+    match version {
+        DataDogApiVersion::V1 => to_vec(series),
+        DataDogApiVersion::V2 => to_vec(&DataDogSeriesV2::from(series)),
+    }
+}
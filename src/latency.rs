@@ -0,0 +1,90 @@
+//! Intake latency tracking
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Objective for the latency of DataDog API requests
+///
+/// Breached when the `quantile` of request latencies over the last `window` exceeds `threshold`,
+/// e.g. p95 above 2 seconds over 5 minutes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataDogLatencySlo {
+    /// Quantile of request latencies, between 0 and 1
+    pub quantile: f64,
+    /// Highest acceptable latency at `quantile`
+    pub threshold: Duration,
+    /// Rolling window of requests considered
+    pub window: Duration,
+}
+
+/// Change of [`DataDogLatencySlo`] state, passed to the latency callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataDogLatencyAlert {
+    /// Whether the objective is now breached, `false` when it recovered
+    pub breached: bool,
+    /// Latency at the objective's quantile over the window
+    pub latency: Duration,
+    /// Number of requests in the window
+    pub requests: usize,
+}
+
+pub(crate) type LatencyCallback = Arc<dyn Fn(&DataDogLatencyAlert) + Send + Sync>;
+
+/// Rolling window of request latencies, checked against a [`DataDogLatencySlo`]
+pub(crate) struct LatencyTracker {
+    slo: DataDogLatencySlo,
+    callback: LatencyCallback,
+    state: Mutex<LatencyState>,
+}
+
+#[derive(Default)]
+struct LatencyState {
+    samples: VecDeque<(Instant, Duration)>,
+    breached: bool,
+}
+
+impl LatencyTracker {
+    pub(crate) fn new(slo: DataDogLatencySlo, callback: LatencyCallback) -> Self {
+        LatencyTracker {
+            slo,
+            callback,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Record the latency of a request, calling the callback if the objective changed state
+    pub(crate) fn record(&self, latency: Duration) {
+        let now = Instant::now();
+        let alert = {
+            let mut state = self.state.lock();
+            state.samples.push_back((now, latency));
+            while let Some((at, _)) = state.samples.front() {
+                if now.duration_since(*at) <= self.slo.window {
+                    break;
+                }
+                state.samples.pop_front();
+            }
+
+            let mut latencies = state.samples.iter().map(|(_, l)| *l).collect::<Vec<_>>();
+            latencies.sort_unstable();
+            let rank = (self.slo.quantile.clamp(0.0, 1.0) * (latencies.len() - 1) as f64).round();
+            let latency = latencies[rank as usize];
+
+            let breached = latency > self.slo.threshold;
+            if breached == state.breached {
+                return;
+            }
+            state.breached = breached;
+            DataDogLatencyAlert {
+                breached,
+                latency,
+                requests: latencies.len(),
+            }
+        };
+        // Called without the lock held, so the callback may flush
+        (self.callback)(&alert);
+    }
+}
@@ -2,24 +2,193 @@
 
 //! Exports any metrics to DataDog
 
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 use metrics::SetRecorderError;
 use std::io;
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 use std::sync::Arc;
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 use std::time::Duration;
 use thiserror::Error;
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 use tokio::task::JoinHandle;
 
+#[cfg(feature = "exporter")]
+mod api_key;
+#[cfg(feature = "exporter")]
+pub use crate::api_key::DataDogApiKeyProvider;
+#[cfg(feature = "exporter")]
+mod assertions;
+#[cfg(feature = "exporter")]
+pub use crate::assertions::DataDogFlushViolation;
+#[cfg(feature = "exporter")]
+mod backfill;
+#[cfg(feature = "exporter")]
+pub use crate::backfill::DataDogBackfillProgress;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "exporter")]
+mod build_info;
+#[cfg(feature = "exporter")]
 mod builder;
+#[cfg(feature = "exporter")]
 pub use crate::builder::DataDogBuilder;
+#[cfg(feature = "exporter")]
+mod checkpoint;
+#[cfg(feature = "exporter")]
+mod chunk;
+#[cfg(feature = "exporter")]
+mod clock;
+#[cfg(feature = "exporter")]
+mod collector;
+#[cfg(feature = "compliance")]
+pub mod compliance;
+#[cfg(feature = "exporter")]
+mod concurrency;
 pub mod data;
+pub use crate::data::DataDogApiVersion;
+pub use crate::data::DataDogCounterTemporality;
+pub use crate::data::DataDogGaugeAggregation;
+pub use crate::data::DataDogHistogramAggregate;
 pub use crate::data::DataDogMetric;
 pub use crate::data::DataDogMetricType;
 pub use crate::data::DataDogMetricValue;
+pub use crate::data::DataDogSeriesType;
+pub use crate::data::DataDogSeriesTypePolicy;
+pub use crate::data::DataDogTagFilter;
+#[cfg(feature = "debugging")]
+pub mod debugging;
 pub use metrics;
+#[cfg(feature = "exporter")]
+mod destination;
+#[cfg(feature = "exporter")]
+pub use crate::destination::DataDogDestination;
+#[cfg(feature = "exporter")]
+mod dogstatsd;
+#[cfg(feature = "exporter")]
+pub mod empty;
+#[cfg(feature = "exporter")]
+pub use crate::empty::DataDogEmptyFlush;
+#[cfg(feature = "exporter")]
+pub use crate::empty::DataDogRegistryStats;
+#[cfg(feature = "exporter")]
 pub mod exporter;
+#[cfg(feature = "exporter")]
+pub use crate::exporter::export_once;
+#[cfg(feature = "exporter")]
 pub use crate::exporter::DataDogExporter;
+#[cfg(feature = "failpoints")]
+pub mod failpoints;
+#[cfg(feature = "exporter")]
+mod file_sink;
+#[cfg(feature = "exporter")]
+pub use crate::file_sink::DataDogFileSink;
+#[cfg(feature = "exporter")]
+mod idempotency;
+#[cfg(feature = "exporter")]
+mod idle;
+#[cfg(feature = "exporter")]
+mod jitter;
+#[cfg(feature = "exporter")]
+mod json;
+#[cfg(feature = "exporter")]
+pub use crate::jitter::DataDogJitter;
+#[cfg(feature = "exporter")]
+pub mod latency;
+#[cfg(feature = "exporter")]
+pub use crate::latency::DataDogLatencyAlert;
+#[cfg(feature = "exporter")]
+pub use crate::latency::DataDogLatencySlo;
+#[cfg(feature = "exporter")]
+mod histograms;
+#[cfg(feature = "exporter")]
+mod memory;
+#[cfg(feature = "exporter")]
+pub use crate::memory::DataDogMemoryPressure;
+pub mod metadata;
+pub use crate::metadata::DataDogMetadata;
+#[cfg(all(windows, feature = "windows-perf"))]
+mod perf;
+#[cfg(all(windows, feature = "windows-perf"))]
+pub use crate::perf::DataDogPerfCounters;
+#[cfg(feature = "exporter")]
+mod preset;
+#[cfg(feature = "exporter")]
+pub use crate::preset::DataDogPreset;
+#[cfg(any(feature = "recorder", feature = "exporter"))]
+mod naming;
+#[cfg(feature = "exporter")]
+mod negotiation;
+#[cfg(feature = "exporter")]
+pub use crate::negotiation::DataDogContentEncoding;
+#[cfg(feature = "exporter")]
+mod priority;
+#[cfg(feature = "exporter")]
+mod proxy;
+#[cfg(feature = "exporter")]
+pub use crate::proxy::DataDogProxy;
+#[cfg(feature = "exporter")]
+mod recent;
+#[cfg(feature = "exporter")]
+pub use crate::recent::DataDogFlushSnapshot;
+#[cfg(feature = "recorder")]
 mod recorder;
+#[cfg(feature = "recorder")]
 pub use crate::recorder::DataDogRecorder;
+#[cfg(all(feature = "recorder", feature = "exporter"))]
+mod reservoir;
+#[cfg(feature = "exporter")]
+pub mod retry;
+#[cfg(feature = "exporter")]
+pub use crate::retry::DataDogRetryPolicy;
+#[cfg(feature = "exporter")]
+mod routing;
+#[cfg(feature = "exporter")]
+mod shadow;
+#[cfg(feature = "exporter")]
+mod signer;
+#[cfg(feature = "exporter")]
+mod sink;
+#[cfg(feature = "exporter")]
+pub use crate::sink::{DataDogPayload, DataDogSink};
+#[cfg(feature = "exporter")]
+mod site;
+#[cfg(feature = "exporter")]
+pub use crate::signer::DataDogRequestSigner;
+#[cfg(feature = "exporter")]
+pub use crate::site::DataDogSite;
+#[cfg(all(feature = "recorder", feature = "exporter"))]
+mod sketch;
+#[cfg(feature = "exporter")]
+mod source;
+#[cfg(feature = "exporter")]
+pub use crate::source::DataDogMetricSource;
+#[cfg(feature = "exporter")]
+pub mod status;
+#[cfg(feature = "exporter")]
+mod tags;
+#[cfg(feature = "exporter")]
+pub use crate::tags::DataDogTags;
+#[cfg(feature = "exporter")]
+mod template;
+#[cfg(feature = "exporter")]
+pub use crate::status::DataDogDeliveryReport;
+#[cfg(feature = "exporter")]
+pub use crate::status::DataDogDestinationStatus;
+#[cfg(feature = "exporter")]
+pub use crate::status::DataDogFlushErrors;
+#[cfg(feature = "exporter")]
+pub use crate::status::DataDogFlushStats;
+#[cfg(feature = "exporter")]
+pub use crate::status::DataDogStatus;
+pub mod units;
+pub use crate::units::DataDogUnit;
+#[cfg(feature = "recorder")]
+mod validation;
+#[cfg(feature = "exporter")]
+mod worker;
+#[cfg(feature = "recorder")]
+pub use crate::validation::DataDogValidation;
 
 /// Error handling metrics
 #[derive(Error, Debug)]
@@ -27,18 +196,116 @@ pub enum Error {
     /// Error when serializing metric to JSON
     #[error("Serialization failed: `{0}`")]
     SerializationError(#[from] serde_json::Error),
+    /// Error when serializing metric to JSON with simd-json
+    #[cfg(feature = "simd-json")]
+    #[error("Serialization failed: `{0}`")]
+    SimdJsonError(#[from] simd_json::Error),
     /// Error when interacting with DataDog API
+    #[cfg(feature = "exporter")]
     #[error("API Request Failed: `{0}`")]
     ApiError(#[from] reqwest::Error),
+    /// Error when a request to the DataDog API fails
+    #[cfg(feature = "exporter")]
+    #[error("API Request `{request_id}` Failed: `{source}`")]
+    RequestFailed {
+        /// Value of the `x-request-id` header
+        request_id: String,
+        /// Underlying error
+        source: reqwest::Error,
+    },
+    /// Error when some requests of a flush failed
+    #[cfg(feature = "exporter")]
+    #[error("Flush failed: {0}")]
+    FlushFailed(DataDogFlushErrors),
+    /// Error injected through a failpoint
+    #[cfg(feature = "failpoints")]
+    #[error("Injected failure: {0:?}")]
+    Injected(failpoints::FailAction),
+    /// Error when a payload would not be accepted by the DataDog API
+    #[cfg(feature = "compliance")]
+    #[error("Payload is not compliant: {violations:?}")]
+    NonCompliantPayload {
+        /// Violations of the DataDog schema or size limits
+        violations: Vec<String>,
+    },
     /// Error compressing or decompressing
     #[error("IO error: `{0}`")]
     IOError(#[from] io::Error),
+    /// Error when a blocking task panicked or was cancelled
+    #[cfg(feature = "exporter")]
+    #[error("Blocking task failed: `{0}`")]
+    TaskError(#[from] tokio::task::JoinError),
+    /// Error when a single point of a series exceeds the DataDog payload limits on its own
+    #[error("Series `{metric}` is too large to submit ({bytes} bytes)")]
+    SeriesTooLarge {
+        /// Metric name
+        metric: String,
+        /// Encoded size of the payload containing only this series and point
+        bytes: usize,
+    },
+    /// Error when an encoded payload exceeds the DataDog payload limits, checked before sending
+    #[error("Payload of {bytes} bytes exceeds the DataDog limits, series: {series:?}")]
+    PayloadTooLarge {
+        /// Names of the metrics encoded into the payload
+        series: Vec<String>,
+        /// Size of the payload
+        bytes: usize,
+    },
+    /// Error when a tag value refers to an unknown context value or environment variable
+    #[error("Unresolved placeholder `{placeholder}` in tag value `{value}`")]
+    UnresolvedTag {
+        /// Tag value
+        value: String,
+        /// Placeholder that could not be resolved
+        placeholder: String,
+    },
+    /// Error when a tag would be rejected or mangled by DataDog
+    #[error("Invalid tag `{tag}`: {reason}")]
+    InvalidTag {
+        /// Tag, as `key:value`
+        tag: String,
+        /// What is wrong with the tag
+        reason: &'static str,
+    },
+    /// Error when parsing a domain that isn't a DataDog site
+    #[error("Unknown DataDog site `{site}`")]
+    UnknownSite {
+        /// Domain parsed
+        site: String,
+    },
+    /// Error when writing to the DataDog API without an API key
+    #[error("DataDog API key is not configured")]
+    MissingApiKey,
+    /// Error when strict intake checks reject a DataDog API response
+    #[cfg(feature = "exporter")]
+    #[error("DataDog API rejected request `{request_id}` with status {status}: {errors:?}")]
+    IntakeRejected {
+        /// Value of the `x-request-id` header
+        request_id: String,
+        /// Response status
+        status: reqwest::StatusCode,
+        /// Errors reported in the response body
+        errors: Vec<String>,
+    },
+    /// Error when writing to the DataDog metadata API without an application key
+    #[error("DataDog application key is not configured")]
+    MissingAppKey,
+    /// Error when writing to stdout without the `stdout` feature
+    #[error("Writing to stdout is disabled, enable the `stdout` feature")]
+    StdoutDisabled,
+    /// Error when configuring TLS without the `rustls` or `native-tls` feature
+    #[error("TLS is disabled, enable the `rustls` or `native-tls` feature")]
+    TlsDisabled,
+    /// Error when writing to the DataDog API without an HTTP client
+    #[error("DataDog API client is not configured")]
+    MissingApiClient,
 }
 
 /// [`Ok`] or [`enum@Error`]
 pub type Result<T, E = Error> = core::result::Result<T, E>;
 
 /// Handle to metrics
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 pub struct DataDogHandle {
     /// Metric recorder
     pub recorder: DataDogRecorder,
@@ -46,6 +313,7 @@ pub struct DataDogHandle {
     pub handle: DataDogExporter,
 }
 
+#[cfg(all(feature = "recorder", feature = "exporter"))]
 impl DataDogHandle {
     /// Install [`DataDogRecorder`] and return [`DataDogExporter`]
     pub fn install(self) -> Result<DataDogExporter, SetRecorderError<DataDogRecorder>> {
@@ -0,0 +1,120 @@
+//! Soft limit on the memory held by the output of a flush
+
+use std::collections::BTreeMap;
+
+use tracing::warn;
+
+use crate::data::{DataDogMetric, DataDogMetricValue};
+use crate::priority::is_high_priority;
+
+/// Share of the budget above which load is shed
+const SHED_THRESHOLD: f64 = 0.8;
+
+/// Memory held by the exporter at the last flush, see
+/// [`DataDogBuilder::memory_budget`](crate::DataDogBuilder::memory_budget)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DataDogMemoryPressure {
+    /// Budget in bytes
+    pub budget: usize,
+    /// Estimate of the bytes held by the collected metrics, mostly histogram observations, and
+    /// the payloads held back for a later flush, before shedding
+    pub usage: usize,
+    /// Usage as a share of the budget, load is shed above 0.8
+    pub pressure: f64,
+    /// Number of series dropped by the last flush to relieve the pressure
+    pub shed_series: usize,
+    /// Number of held back payloads dropped by the last flush to relieve the pressure
+    pub shed_payloads: usize,
+}
+
+/// Sheds load once the estimated memory held by a flush approaches the budget
+///
+/// Metrics are estimated once collected, so this caps what a flush holds on to and submits, not
+/// the memory recorded between flushes.
+pub(crate) struct MemoryBudget {
+    bytes: usize,
+    high_priority: Vec<String>,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(bytes: usize, high_priority: Vec<String>) -> Self {
+        MemoryBudget {
+            bytes,
+            high_priority,
+        }
+    }
+
+    /// Drop the oldest of the `deferred` payload sizes, then low priority series, until the
+    /// usage is back under the shedding threshold
+    ///
+    /// Returns the pressure before shedding, the number of deferred payloads to drop from the
+    /// front and the number of series dropped per metric
+    pub(crate) fn shed(
+        &self,
+        metrics: &mut Vec<DataDogMetric>,
+        deferred: &[usize],
+    ) -> (DataDogMemoryPressure, usize, BTreeMap<String, usize>) {
+        let threshold = (self.bytes as f64 * SHED_THRESHOLD) as usize;
+        let metrics_bytes = metrics.iter().map(estimate).sum::<usize>();
+        let deferred_bytes = deferred.iter().sum::<usize>();
+        let usage = metrics_bytes + deferred_bytes;
+        let mut pressure = DataDogMemoryPressure {
+            budget: self.bytes,
+            usage,
+            pressure: usage as f64 / self.bytes.max(1) as f64,
+            ..DataDogMemoryPressure::default()
+        };
+        let mut dropped = BTreeMap::new();
+        if usage <= threshold {
+            return (pressure, 0, dropped);
+        }
+
+        // Payloads held back are older than the metrics of this flush
+        let mut remaining = usage;
+        for bytes in deferred {
+            if remaining <= threshold {
+                break;
+            }
+            remaining -= bytes;
+            pressure.shed_payloads += 1;
+        }
+
+        // Low priority series first, the latest first, so the rest keep their order
+        let mut shed = vec![false; metrics.len()];
+        for high_priority in [false, true] {
+            for (index, metric) in metrics.iter().enumerate().rev() {
+                if remaining <= threshold {
+                    break;
+                }
+                if is_high_priority(&self.high_priority, &metric.metric) == high_priority {
+                    remaining -= estimate(metric);
+                    shed[index] = true;
+                }
+            }
+        }
+        let mut shed = shed.into_iter();
+        metrics.retain(|metric| {
+            let shed = shed.next().unwrap_or_default();
+            if shed {
+                *dropped.entry(metric.metric.clone()).or_insert(0) += 1;
+            }
+            !shed
+        });
+        pressure.shed_series = dropped.values().sum();
+
+        warn!(
+            budget = self.bytes,
+            usage,
+            shed_payloads = pressure.shed_payloads,
+            dropped = ?dropped,
+            "Memory budget approached, shed held back payloads and lowest priority series"
+        );
+        (pressure, pressure.shed_payloads, dropped)
+    }
+}
+
+/// Rough size of `metric` in memory
+fn estimate(metric: &DataDogMetric) -> usize {
+    let tags = metric.tags.iter().map(String::len).sum::<usize>();
+    metric.metric.len() + tags + metric.points.len() * std::mem::size_of::<DataDogMetricValue>()
+}
@@ -0,0 +1,60 @@
+//! Metric metadata
+
+#[cfg(any(feature = "recorder", feature = "exporter"))]
+use std::collections::HashMap;
+#[cfg(any(feature = "recorder", feature = "exporter"))]
+use std::sync::Arc;
+
+#[cfg(any(feature = "recorder", feature = "exporter"))]
+use parking_lot::RwLock;
+#[cfg(feature = "exporter")]
+use serde::Serialize;
+#[cfg(feature = "exporter")]
+use serde_with::skip_serializing_none;
+
+use crate::data::DataDogMetricType;
+#[cfg(feature = "exporter")]
+use crate::data::{DataDogSeriesType, DataDogSeriesTypePolicy};
+use crate::units::DataDogUnit;
+
+/// Metadata of described metrics, keyed by metric name
+#[cfg(any(feature = "recorder", feature = "exporter"))]
+pub(crate) type MetadataStore = Arc<RwLock<HashMap<String, DataDogMetadata>>>;
+
+/// Unit and description of a metric
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDogMetadata {
+    /// Metric type
+    pub metric_type: DataDogMetricType,
+    /// Metric unit
+    pub unit: Option<DataDogUnit>,
+    /// Metric description
+    pub description: String,
+}
+
+/// DataDog metadata API body
+#[cfg(feature = "exporter")]
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+pub(crate) struct DataDogMetadataUpdate<'a> {
+    #[serde(rename = "type")]
+    metric_type: DataDogSeriesType,
+    description: &'a str,
+    unit: Option<&'a str>,
+    per_unit: Option<&'a str>,
+}
+
+#[cfg(feature = "exporter")]
+impl<'a> DataDogMetadataUpdate<'a> {
+    pub(crate) fn new(
+        metadata: &'a DataDogMetadata,
+        series_types: &DataDogSeriesTypePolicy,
+    ) -> Self {
+        DataDogMetadataUpdate {
+            metric_type: series_types.series_type(&metadata.metric_type),
+            description: &metadata.description,
+            unit: metadata.unit.as_ref().map(DataDogUnit::unit),
+            per_unit: metadata.unit.as_ref().and_then(DataDogUnit::per_unit),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! DataDog naming rules of metrics and tags, shared by the builder, the recorder and the
+//! compliance checks
+//!
+//! Rules from <https://docs.datadoghq.com/metrics/custom_metrics/#naming-custom-metrics> and
+//! <https://docs.datadoghq.com/getting_started/tagging/#define-tags>.
+
+/// DataDog rejects metric names longer than this
+#[cfg(any(feature = "recorder", feature = "compliance"))]
+pub(crate) const MAX_NAME_LENGTH: usize = 200;
+/// DataDog rejects tags longer than this, including the separator
+pub(crate) const MAX_TAG_LENGTH: usize = 200;
+
+/// Why DataDog would reject or rewrite the metric `name`, [`None`] if it is valid
+#[cfg(feature = "recorder")]
+pub(crate) fn name_violation(name: &str) -> Option<&'static str> {
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Some("name must start with a letter");
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Some("name is longer than 200 characters");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    {
+        return Some("name holds a character other than letters, digits, `_` and `.`");
+    }
+    None
+}
+
+/// Why DataDog would reject or rewrite the tag `key:value`, or the simple tag `key` without a
+/// value, [`None`] if it is valid
+///
+/// Keys start with a letter and hold letters, digits, `_`, `-`, `.` and `/`. Values can't be empty
+/// and hold letters, digits, `_`, `-`, `:`, `.` and `/`, other characters are replaced with `_`.
+pub(crate) fn tag_violation(key: &str, value: Option<&str>) -> Option<&'static str> {
+    if !key.starts_with(char::is_alphabetic) {
+        return Some("key must start with a letter");
+    }
+    if let Some(c) = key
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/')))
+    {
+        return Some(if c == ':' || c == '=' {
+            "key holds a separator, pass the value separately"
+        } else {
+            "key holds a character other than letters, digits, `_`, `-`, `.` and `/`"
+        });
+    }
+    let length = match value {
+        Some("") => return Some("value is empty"),
+        Some(value)
+            if !value
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '/')) =>
+        {
+            return Some(
+                "value holds a character other than letters, digits, `_`, `-`, `:`, `.` and `/`",
+            );
+        }
+        Some(value) => key.len() + 1 + value.len(),
+        None => key.len(),
+    };
+    if length > MAX_TAG_LENGTH {
+        return Some("tag is longer than 200 characters");
+    }
+    None
+}
@@ -0,0 +1,168 @@
+//! Content encodings accepted by each DataDog API host
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parking_lot::Mutex;
+
+use crate::exporter::MAX_PAYLOAD_BYTES;
+use crate::{Error, Result};
+
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+pub(crate) const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Encoding of request payloads, set with
+/// [`DataDogBuilder::accepted_encodings`](crate::DataDogBuilder::accepted_encodings)
+///
+/// ```
+/// # use metrics_datadog_exporter::{DataDogBuilder, DataDogContentEncoding};
+/// // The proxy rejects zstd, the DataDog API accepts it
+/// let builder = DataDogBuilder::default()
+///     .api_host("https://proxy.internal/api/v1".to_string())
+///     .accepted_encodings(
+///         "https://proxy.internal/api/v1".to_string(),
+///         vec![DataDogContentEncoding::Gzip],
+///     );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataDogContentEncoding {
+    /// Uncompressed, accepted by every host
+    Identity,
+    /// `Content-Encoding: gzip`
+    Gzip,
+    /// `Content-Encoding: zstd1`
+    Zstd,
+}
+
+impl DataDogContentEncoding {
+    /// Encoding of `body`, from its magic bytes
+    pub(crate) fn of(body: &[u8]) -> Self {
+        if body.starts_with(&GZIP_MAGIC) {
+            DataDogContentEncoding::Gzip
+        } else if body.starts_with(&ZSTD_MAGIC) {
+            DataDogContentEncoding::Zstd
+        } else {
+            DataDogContentEncoding::Identity
+        }
+    }
+
+    /// Value of the `Content-Encoding` header, none when uncompressed
+    pub(crate) fn header(self) -> Option<&'static str> {
+        match self {
+            DataDogContentEncoding::Identity => None,
+            DataDogContentEncoding::Gzip => Some("gzip"),
+            DataDogContentEncoding::Zstd => Some("zstd1"),
+        }
+    }
+
+    fn decode(self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut decoded = vec![];
+        match self {
+            DataDogContentEncoding::Identity => decoded.extend_from_slice(body),
+            DataDogContentEncoding::Gzip => {
+                GzDecoder::new(body).read_to_end(&mut decoded)?;
+            }
+            #[cfg(feature = "zstd")]
+            DataDogContentEncoding::Zstd => decoded = zstd::stream::decode_all(body)?,
+            #[cfg(not(feature = "zstd"))]
+            DataDogContentEncoding::Zstd => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "zstd feature disabled",
+                ))
+            }
+        }
+        Ok(decoded)
+    }
+
+    fn encode(self, body: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match self {
+            DataDogContentEncoding::Identity => Ok(body),
+            DataDogContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(&body)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            DataDogContentEncoding::Zstd => {
+                zstd::stream::encode_all(body.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)
+            }
+            #[cfg(not(feature = "zstd"))]
+            DataDogContentEncoding::Zstd => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "zstd feature disabled",
+            )),
+        }
+    }
+}
+
+/// Encodings accepted by each API host, configured or learnt from 415 Unsupported Media Type
+/// responses
+pub(crate) struct HostEncodings {
+    /// Accepted encodings by host index, none while every encoding is assumed accepted
+    hosts: Vec<Mutex<Option<Vec<DataDogContentEncoding>>>>,
+}
+
+impl HostEncodings {
+    pub(crate) fn new(
+        api_hosts: &[String],
+        accepted: &HashMap<String, Vec<DataDogContentEncoding>>,
+    ) -> Self {
+        HostEncodings {
+            hosts: api_hosts
+                .iter()
+                .map(|host| Mutex::new(accepted.get(host).cloned()))
+                .collect(),
+        }
+    }
+
+    /// Whether `host` is known to accept `encoding`, uncompressed payloads are always accepted
+    pub(crate) fn accepts(&self, host: usize, encoding: DataDogContentEncoding) -> bool {
+        encoding == DataDogContentEncoding::Identity
+            || self.hosts[host]
+                .lock()
+                .as_ref()
+                .is_none_or(|accepted| accepted.contains(&encoding))
+    }
+
+    /// Stop sending `encoding` to `host` after it rejected it
+    pub(crate) fn reject(&self, host: usize, encoding: DataDogContentEncoding) {
+        self.hosts[host]
+            .lock()
+            .get_or_insert_with(|| {
+                vec![
+                    DataDogContentEncoding::Identity,
+                    DataDogContentEncoding::Gzip,
+                    DataDogContentEncoding::Zstd,
+                ]
+            })
+            .retain(|accepted| *accepted != encoding);
+    }
+
+    /// `body` re-encoded, if needed, with the best encoding `host` accepts
+    ///
+    /// Zstd is only picked if `zstd` is set, for intakes accepting it. Fails if the re-encoded
+    /// body exceeds the DataDog payload limits, e.g. a large gzip payload sent uncompressed.
+    pub(crate) fn negotiate(&self, host: usize, body: Vec<u8>, zstd: bool) -> Result<Vec<u8>> {
+        let encoding = DataDogContentEncoding::of(&body);
+        if self.accepts(host, encoding) {
+            return Ok(body);
+        }
+        let target = [DataDogContentEncoding::Gzip, DataDogContentEncoding::Zstd]
+            .into_iter()
+            .filter(|target| *target != DataDogContentEncoding::Zstd || zstd)
+            .find(|target| self.accepts(host, *target))
+            .unwrap_or(DataDogContentEncoding::Identity);
+        let transcoded = target.encode(encoding.decode(&body)?)?;
+        if transcoded.len() >= MAX_PAYLOAD_BYTES {
+            return Err(Error::PayloadTooLarge {
+                series: vec![],
+                bytes: transcoded.len(),
+            });
+        }
+        Ok(transcoded)
+    }
+}
@@ -0,0 +1,143 @@
+//! Windows performance counters
+//!
+//! Samples host metrics into the installed recorder on Windows hosts without the DataDog agent,
+//! using the names the agent reports them under.
+
+use std::io;
+use std::mem::{size_of, zeroed};
+use std::time::Duration;
+
+use metrics::gauge;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use windows_sys::Win32::Foundation::FILETIME;
+use windows_sys::Win32::System::Performance::{
+    PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData, PdhGetFormattedCounterValue,
+    PdhOpenQueryW, PDH_FMT_COUNTERVALUE, PDH_FMT_DOUBLE,
+};
+use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+use windows_sys::Win32::System::Threading::GetSystemTimes;
+
+use crate::Result;
+
+const DISK_QUEUE_COUNTER: &str = r"\PhysicalDisk(_Total)\Current Disk Queue Length";
+
+/// Sampler of CPU, memory and disk queue performance counters
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use metrics_datadog_exporter::DataDogPerfCounters;
+/// # async fn example() -> metrics_datadog_exporter::Result<()> {
+/// DataDogPerfCounters::new()?.schedule(Duration::from_secs(10));
+/// # Ok(())
+/// # }
+/// ```
+pub struct DataDogPerfCounters {
+    query: isize,
+    disk_queue: isize,
+    /// Idle, kernel and user times of the previous sample
+    cpu_times: Option<(u64, u64, u64)>,
+}
+
+impl DataDogPerfCounters {
+    /// Open the performance counter query
+    pub fn new() -> Result<Self> {
+        let mut query = 0;
+        pdh(unsafe { PdhOpenQueryW(std::ptr::null(), 0, &mut query) })?;
+        let mut counters = DataDogPerfCounters {
+            query,
+            disk_queue: 0,
+            cpu_times: None,
+        };
+        let path = wide(DISK_QUEUE_COUNTER);
+        pdh(unsafe { PdhAddEnglishCounterW(query, path.as_ptr(), 0, &mut counters.disk_queue) })?;
+        // Rate counters need two collections, the first one only sets the baseline
+        pdh(unsafe { PdhCollectQueryData(query) })?;
+        Ok(counters)
+    }
+
+    /// Record the current value of every counter as a gauge
+    ///
+    /// CPU usage is measured since the previous sample, so is only recorded from the second one
+    pub fn sample(&mut self) -> Result<()> {
+        if let Some(usage) = self.cpu_usage()? {
+            gauge!("system.cpu.usage").set(usage);
+        }
+
+        let mut memory: MEMORYSTATUSEX = unsafe { zeroed() };
+        memory.dwLength = size_of::<MEMORYSTATUSEX>() as u32;
+        if unsafe { GlobalMemoryStatusEx(&mut memory) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        gauge!("system.mem.total").set(memory.ullTotalPhys as f64);
+        gauge!("system.mem.usable").set(memory.ullAvailPhys as f64);
+        gauge!("system.mem.used").set((memory.ullTotalPhys - memory.ullAvailPhys) as f64);
+
+        pdh(unsafe { PdhCollectQueryData(self.query) })?;
+        let mut value: PDH_FMT_COUNTERVALUE = unsafe { zeroed() };
+        pdh(unsafe {
+            PdhGetFormattedCounterValue(
+                self.disk_queue,
+                PDH_FMT_DOUBLE,
+                std::ptr::null_mut(),
+                &mut value,
+            )
+        })?;
+        gauge!("system.io.avg_q_sz").set(unsafe { value.Anonymous.doubleValue });
+        Ok(())
+    }
+
+    /// Sample counters every [`Duration`], logging failed samples
+    pub fn schedule(mut self, period: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sample() {
+                    tracing::warn!(error = ?e, "Failed to sample performance counters");
+                }
+            }
+        })
+    }
+
+    /// Percentage of CPU time spent busy since the previous sample
+    fn cpu_usage(&mut self) -> Result<Option<f64>> {
+        let mut idle: FILETIME = unsafe { zeroed() };
+        let mut kernel: FILETIME = unsafe { zeroed() };
+        let mut user: FILETIME = unsafe { zeroed() };
+        if unsafe { GetSystemTimes(&mut idle, &mut kernel, &mut user) } == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let times = (ticks(&idle), ticks(&kernel), ticks(&user));
+        let usage = self.cpu_times.replace(times).and_then(|previous| {
+            // Kernel time includes idle time
+            let total = (times.1 - previous.1) + (times.2 - previous.2);
+            let idle = times.0 - previous.0;
+            (total > 0).then(|| (total - idle) as f64 * 100.0 / total as f64)
+        });
+        Ok(usage)
+    }
+}
+
+impl Drop for DataDogPerfCounters {
+    fn drop(&mut self) {
+        unsafe { PdhCloseQuery(self.query) };
+    }
+}
+
+fn ticks(time: &FILETIME) -> u64 {
+    (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime)
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn pdh(status: u32) -> Result<()> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("PDH call failed with status {status:#x}")).into())
+    }
+}
@@ -0,0 +1,62 @@
+//! Builder presets
+
+use std::time::Duration;
+
+use crate::DataDogBuilder;
+
+/// Bundle of builder settings for a kind of deployment
+///
+/// Applied with [`DataDogBuilder::preset`], settings made after it override the preset's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDogPreset {
+    /// Services recording many series: sharded senders, compression off the async executor and
+    /// connections kept warm
+    HighThroughput,
+    /// Edge deployments with few series and constrained links: compressed payloads and a series
+    /// budget guarding against accidental cardinality
+    LowCardinalityEdge,
+    /// Short-lived functions: work done inline without background threads, a short request
+    /// timeout
+    Serverless,
+    /// Local development: metrics written to stdout only, uncompressed, and not written at all
+    /// without the `stdout` feature
+    Development,
+}
+
+impl DataDogPreset {
+    /// Flush interval suited to the preset, to pass to
+    /// [`DataDogExporter::schedule`](crate::DataDogExporter::schedule)
+    pub fn interval(&self) -> Duration {
+        match self {
+            DataDogPreset::HighThroughput => Duration::from_secs(10),
+            DataDogPreset::LowCardinalityEdge => Duration::from_secs(60),
+            DataDogPreset::Serverless => Duration::from_secs(10),
+            DataDogPreset::Development => Duration::from_secs(1),
+        }
+    }
+
+    pub(crate) fn apply(&self, builder: DataDogBuilder) -> DataDogBuilder {
+        match self {
+            DataDogPreset::HighThroughput => builder
+                .write_to_stdout(false)
+                .gzip(true)
+                .sender_shards(4)
+                .offload_blocking(true)
+                .compression_thread(true)
+                .keepalive(Duration::from_secs(30)),
+            DataDogPreset::LowCardinalityEdge => {
+                builder.write_to_stdout(false).gzip(true).max_series(1000)
+            }
+            DataDogPreset::Serverless => builder
+                .write_to_stdout(false)
+                .gzip(true)
+                .offload_blocking(false)
+                .compression_thread(false)
+                .client_timeout(Duration::from_secs(5)),
+            DataDogPreset::Development => builder
+                .write_to_stdout(cfg!(feature = "stdout"))
+                .write_to_api(false, None)
+                .gzip(false),
+        }
+    }
+}
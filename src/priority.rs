@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use tracing::warn;
+
+use crate::data::DataDogMetric;
+
+/// Sheds the series submitted per flush, low priority series first, while rate limited or over
+/// the series limit
+pub(crate) struct SeriesBudget {
+    limit: Option<usize>,
+    high_priority: Vec<String>,
+}
+
+impl SeriesBudget {
+    pub(crate) fn new(limit: Option<usize>, high_priority: Vec<String>) -> Self {
+        SeriesBudget {
+            limit,
+            high_priority,
+        }
+    }
+
+    /// Drop every low priority series if `rate_limited` and some metrics are high priority, then
+    /// series over the limit, low priority first, returning the number dropped per metric
+    ///
+    /// The series kept are in their original order.
+    pub(crate) fn shed(
+        &self,
+        metrics: &mut Vec<DataDogMetric>,
+        rate_limited: bool,
+    ) -> BTreeMap<String, usize> {
+        let high_priority = metrics
+            .iter()
+            .filter(|metric| is_high_priority(&self.high_priority, &metric.metric))
+            .count();
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let mut high_left = limit;
+        let mut low_left = if rate_limited && !self.high_priority.is_empty() {
+            0
+        } else {
+            limit.saturating_sub(high_priority)
+        };
+
+        let mut dropped = BTreeMap::new();
+        metrics.retain(|metric| {
+            let left = if is_high_priority(&self.high_priority, &metric.metric) {
+                &mut high_left
+            } else {
+                &mut low_left
+            };
+            if *left == 0 {
+                *dropped.entry(metric.metric.clone()).or_insert(0) += 1;
+                return false;
+            }
+            *left -= 1;
+            true
+        });
+        if !dropped.is_empty() {
+            warn!(
+                limit = self.limit,
+                rate_limited,
+                dropped = ?dropped,
+                "Series budget exceeded, dropped lowest priority series"
+            );
+        }
+        dropped
+    }
+}
+
+/// Whether `metric` is one of the `high_priority` metrics or is in one of their namespaces
+pub(crate) fn is_high_priority(high_priority: &[String], metric: &str) -> bool {
+    high_priority.iter().any(|name| {
+        metric
+            .strip_prefix(name.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
@@ -0,0 +1,62 @@
+//! Proxy configuration for requests to the DataDog API
+
+use reqwest::{NoProxy, Proxy};
+
+use crate::Result;
+
+/// Proxy sending every request to the DataDog API, set with
+/// [`DataDogBuilder::proxy`](crate::DataDogBuilder::proxy)
+///
+/// ```
+/// # use metrics_datadog_exporter::{DataDogBuilder, DataDogProxy};
+/// let builder = DataDogBuilder::default().proxy(
+///     DataDogProxy::new("http://proxy.internal:3128")
+///         .basic_auth("metrics", "secret")
+///         .no_proxy(vec!["localhost".to_string(), ".internal".to_string()]),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDogProxy {
+    url: String,
+    basic_auth: Option<(String, String)>,
+    no_proxy: Vec<String>,
+}
+
+impl DataDogProxy {
+    /// Proxy at `url`, e.g. `http://proxy.internal:3128`
+    pub fn new(url: impl Into<String>) -> Self {
+        DataDogProxy {
+            url: url.into(),
+            basic_auth: None,
+            no_proxy: vec![],
+        }
+    }
+
+    /// Authenticate to the proxy with basic auth
+    #[must_use]
+    pub fn basic_auth(self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        DataDogProxy {
+            basic_auth: Some((username.into(), password.into())),
+            ..self
+        }
+    }
+
+    /// Connect directly to `hosts`, as in `NO_PROXY`: domains, matching their subdomains too, IP
+    /// addresses or CIDR blocks
+    #[must_use]
+    pub fn no_proxy(self, hosts: Vec<String>) -> Self {
+        DataDogProxy {
+            no_proxy: hosts,
+            ..self
+        }
+    }
+
+    /// Fails with [`Error::ApiError`](crate::Error::ApiError) if the URL is invalid
+    pub(crate) fn to_reqwest(&self) -> Result<Proxy> {
+        let mut proxy = Proxy::all(&self.url)?;
+        if let Some((username, password)) = &self.basic_auth {
+            proxy = proxy.basic_auth(username, password);
+        }
+        Ok(proxy.no_proxy(NoProxy::from_string(&self.no_proxy.join(","))))
+    }
+}
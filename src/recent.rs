@@ -0,0 +1,52 @@
+//! Recent flushes kept for debugging
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+
+use crate::data::DataDogMetric;
+
+/// Metrics written by a flush, after transforms and before sending
+#[derive(Debug, Clone)]
+pub struct DataDogFlushSnapshot {
+    /// Time of the flush
+    pub timestamp: DateTime<Utc>,
+    /// Metrics written
+    pub metrics: Vec<DataDogMetric>,
+}
+
+/// Ring buffer of the last flush snapshots
+pub(crate) struct RecentFlushes {
+    capacity: usize,
+    flushes: Mutex<VecDeque<DataDogFlushSnapshot>>,
+}
+
+impl RecentFlushes {
+    pub(crate) fn new(capacity: usize) -> Self {
+        RecentFlushes {
+            capacity,
+            flushes: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Keep a copy of `metrics`, evicting the oldest snapshot when full
+    pub(crate) fn record(&self, metrics: &[DataDogMetric]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut flushes = self.flushes.lock();
+        if flushes.len() == self.capacity {
+            flushes.pop_front();
+        }
+        flushes.push_back(DataDogFlushSnapshot {
+            timestamp: Utc::now(),
+            metrics: metrics.to_vec(),
+        });
+    }
+
+    /// Snapshots, oldest first
+    pub(crate) fn snapshots(&self) -> Vec<DataDogFlushSnapshot> {
+        self.flushes.lock().iter().cloned().collect()
+    }
+}
@@ -1,42 +1,111 @@
 use std::sync::Arc;
 
 use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
-use metrics_util::registry::{AtomicStorage, Registry};
+use metrics_util::registry::{GenerationalAtomicStorage, Registry};
+
+use crate::data::DataDogMetricType;
+#[cfg(feature = "exporter")]
+use crate::histograms::HistogramStores;
+use crate::metadata::{DataDogMetadata, MetadataStore};
+use crate::units::DataDogUnit;
+use crate::validation::{DataDogValidation, WarnedKeys};
 
 /// Metric recorder
 pub struct DataDogRecorder {
-    registry: Arc<Registry<Key, AtomicStorage>>,
+    registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+    metadata: MetadataStore,
+    #[cfg(feature = "exporter")]
+    histograms: HistogramStores,
+    validation: DataDogValidation,
+    warned: WarnedKeys,
 }
 
 impl DataDogRecorder {
-    pub(crate) fn new(registry: Arc<Registry<Key, AtomicStorage>>) -> Self {
-        DataDogRecorder { registry }
+    pub(crate) fn new(
+        registry: Arc<Registry<Key, GenerationalAtomicStorage>>,
+        metadata: MetadataStore,
+        validation: DataDogValidation,
+    ) -> Self {
+        DataDogRecorder {
+            registry,
+            metadata,
+            #[cfg(feature = "exporter")]
+            histograms: HistogramStores::default(),
+            validation,
+            warned: WarnedKeys::default(),
+        }
+    }
+
+    /// Record histograms into `histograms` instead of the registry where they store them
+    #[cfg(feature = "exporter")]
+    pub(crate) fn with_histograms(self, histograms: HistogramStores) -> Self {
+        DataDogRecorder { histograms, ..self }
+    }
+
+    /// Registry metrics are recorded in, for collection by an exporter elsewhere
+    pub fn registry(&self) -> &Arc<Registry<Key, GenerationalAtomicStorage>> {
+        &self.registry
+    }
+
+    fn describe(
+        &self,
+        key: KeyName,
+        metric_type: DataDogMetricType,
+        unit: Option<Unit>,
+        description: SharedString,
+    ) {
+        self.metadata.write().insert(
+            key.as_str().to_string(),
+            DataDogMetadata {
+                metric_type,
+                unit: unit.map(DataDogUnit::from),
+                description: description.into_owned(),
+            },
+        );
+    }
+}
+
+impl Default for DataDogRecorder {
+    /// Recorder with its own registry, validating with the default [`DataDogValidation`]
+    fn default() -> Self {
+        DataDogRecorder::new(
+            Arc::new(Registry::new(GenerationalAtomicStorage::atomic())),
+            MetadataStore::default(),
+            DataDogValidation::default(),
+        )
     }
 }
 
 impl Recorder for DataDogRecorder {
-    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, DataDogMetricType::Count, unit, description)
     }
 
-    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, DataDogMetricType::Gauge, unit, description)
     }
 
-    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, DataDogMetricType::Histogram, unit, description)
     }
 
     fn register_counter(&self, key: &Key, _: &Metadata) -> Counter {
+        self.validation.check(key, &self.warned);
         self.registry
             .get_or_create_counter(key, |c| c.clone().into())
     }
 
     fn register_gauge(&self, key: &Key, _: &Metadata) -> Gauge {
+        self.validation.check(key, &self.warned);
         self.registry.get_or_create_gauge(key, |c| c.clone().into())
     }
 
     fn register_histogram(&self, key: &Key, _: &Metadata) -> Histogram {
+        self.validation.check(key, &self.warned);
+        #[cfg(feature = "exporter")]
+        if let Some(histogram) = self.histograms.histogram(key) {
+            return histogram;
+        }
         self.registry
             .get_or_create_histogram(key, |c| c.clone().into())
     }
@@ -0,0 +1,135 @@
+//! Histograms keeping a bounded random sample of their observations
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use metrics::{Histogram, HistogramFn, Key};
+use parking_lot::Mutex;
+
+/// Uniform sample of at most `limit` observations of a histogram, by reservoir sampling
+struct Reservoir {
+    limit: usize,
+    samples: Vec<f64>,
+    /// Observations recorded, kept or not
+    seen: u64,
+    /// State of the xorshift generator picking the samples to replace
+    state: u64,
+}
+
+impl Reservoir {
+    fn new(limit: usize) -> Self {
+        Reservoir {
+            limit,
+            samples: Vec::new(),
+            seen: 0,
+            // Any non-zero seed
+            state: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    /// Record `value`, returning whether an observation was discarded
+    fn record(&mut self, value: f64) -> bool {
+        self.seen += 1;
+        if self.samples.len() < self.limit {
+            self.samples.push(value);
+            return false;
+        }
+        let index = self.next() % self.seen;
+        if let Some(sample) = self.samples.get_mut(index as usize) {
+            *sample = value;
+        }
+        true
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+/// Reservoir of a single histogram, recorded into by its [`Histogram`] handles
+struct KeyReservoir {
+    reservoir: Mutex<Reservoir>,
+    discarded: Arc<AtomicU64>,
+}
+
+impl HistogramFn for KeyReservoir {
+    fn record(&self, value: f64) {
+        if self.reservoir.lock().record(value) {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Reservoirs of the histograms recorded since the last collection, shared by the recorder and
+/// the exporter
+#[derive(Clone)]
+pub(crate) struct ReservoirStore {
+    limit: usize,
+    reservoirs: Arc<Mutex<HashMap<Key, Arc<KeyReservoir>>>>,
+    discarded: Arc<AtomicU64>,
+}
+
+impl ReservoirStore {
+    pub(crate) fn new(limit: usize) -> Self {
+        ReservoirStore {
+            limit,
+            reservoirs: Arc::default(),
+            discarded: Arc::default(),
+        }
+    }
+
+    /// Handle recording into the reservoir of `key`
+    pub(crate) fn histogram(&self, key: &Key) -> Histogram {
+        let reservoir = self
+            .reservoirs
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(KeyReservoir {
+                    reservoir: Mutex::new(Reservoir::new(self.limit)),
+                    discarded: self.discarded.clone(),
+                })
+            })
+            .clone();
+        Histogram::from_arc(reservoir)
+    }
+
+    /// Remove and return the samples of keys matching `filter`
+    pub(crate) fn take(&self, filter: impl Fn(&Key) -> bool) -> Vec<(Key, Vec<f64>)> {
+        let mut reservoirs = self.reservoirs.lock();
+        let taken = reservoirs
+            .keys()
+            .filter(|k| filter(k))
+            .cloned()
+            .collect::<Vec<_>>();
+        taken
+            .into_iter()
+            .filter_map(|key| {
+                let reservoir = reservoirs.remove(&key)?;
+                let samples = std::mem::take(&mut reservoir.reservoir.lock().samples);
+                Some((key, samples))
+            })
+            .collect()
+    }
+
+    /// Drop every reservoir
+    pub(crate) fn clear(&self) {
+        self.reservoirs.lock().clear();
+    }
+
+    /// Number of histograms recorded since the last collection
+    pub(crate) fn len(&self) -> usize {
+        self.reservoirs.lock().len()
+    }
+
+    /// Number of observations discarded by full reservoirs since the store was created
+    pub(crate) fn discarded(&self) -> u64 {
+        self.discarded.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,65 @@
+//! Retries of transient API failures
+
+use std::time::Duration;
+
+use crate::jitter::random_fraction;
+use crate::Error;
+
+/// Policy retrying requests that failed with a transient error, i.e. a network error or a 5xx
+///
+/// Each request payload is retried on its own, waiting `base_delay * 2^(attempt - 1)` capped at
+/// `max_delay` after `attempt` failed attempts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataDogRetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Highest delay between attempts
+    pub max_delay: Duration,
+    /// Fraction of each delay that is randomized, between 0 and 1, so senders don't retry in
+    /// lockstep, none if it isn't finite
+    pub jitter: f64,
+}
+
+impl Default for DataDogRetryPolicy {
+    fn default() -> Self {
+        DataDogRetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl DataDogRetryPolicy {
+    /// Delay before retrying after `attempt` failed attempts
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 || !jitter.is_finite() {
+            return delay;
+        }
+        delay.mul_f64(1.0 - jitter * random_fraction())
+    }
+
+    /// Whether a request failing with `error` after `attempt` attempts should be retried
+    pub(crate) fn retries(&self, error: &Error, attempt: u32) -> bool {
+        attempt < self.max_attempts && is_transient(error)
+    }
+}
+
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::RequestFailed { source, .. } => {
+            source.is_connect()
+                || source.is_timeout()
+                || source.status().is_some_and(|s| s.is_server_error())
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use crate::data::DataDogMetric;
+use crate::sink::DataDogPayload;
+
+/// Selects the API key of each metric from the value of one of its tags
+pub(crate) struct ApiKeyRouting {
+    tag: String,
+    keys: HashMap<String, String>,
+}
+
+impl ApiKeyRouting {
+    pub(crate) fn new(tag: String, keys: HashMap<String, String>) -> Self {
+        ApiKeyRouting { tag, keys }
+    }
+
+    /// API key of a metric, [`None`] for the default API key
+    fn api_key(&self, metric: &DataDogMetric) -> Option<&String> {
+        metric
+            .tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix(self.tag.as_str())?.strip_prefix(':'))
+            .and_then(|value| self.keys.get(value))
+    }
+
+    /// Group metrics by API key into payloads, the default API key first
+    pub(crate) fn route(&self, metrics: Vec<DataDogMetric>) -> Vec<DataDogPayload> {
+        let mut routes: HashMap<Option<String>, Vec<DataDogMetric>> = HashMap::new();
+        for metric in metrics {
+            routes
+                .entry(self.api_key(&metric).cloned())
+                .or_default()
+                .push(metric);
+        }
+        let mut payloads = routes
+            .into_iter()
+            .map(|(api_key, metrics)| DataDogPayload { api_key, metrics })
+            .collect::<Vec<_>>();
+        payloads.sort_by(|a, b| a.api_key.cmp(&b.api_key));
+        payloads
+    }
+}
@@ -0,0 +1,35 @@
+//! Shadow writes for validating migrations
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::data::DataDogMetric;
+
+pub(crate) type ShadowCallback = Arc<dyn Fn(&[DataDogMetric]) + Send + Sync>;
+
+/// Hands a share of flushes to a callback, on top of writing them as usual
+pub(crate) struct Shadow {
+    ratio: f64,
+    callback: ShadowCallback,
+    flushes: AtomicU64,
+}
+
+impl Shadow {
+    /// Shadow `percent` of flushes, clamped between 0 and 100
+    pub(crate) fn new(percent: f64, callback: ShadowCallback) -> Self {
+        Shadow {
+            ratio: percent.clamp(0.0, 100.0) / 100.0,
+            callback,
+            flushes: AtomicU64::new(0),
+        }
+    }
+
+    /// Call the callback with `metrics` if this flush falls in the shadowed share
+    pub(crate) fn write(&self, metrics: &[DataDogMetric]) {
+        // Spread shadowed flushes evenly instead of sampling randomly
+        let flush = self.flushes.fetch_add(1, Ordering::Relaxed) as f64;
+        if ((flush + 1.0) * self.ratio).floor() > (flush * self.ratio).floor() {
+            (self.callback)(metrics);
+        }
+    }
+}
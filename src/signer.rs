@@ -0,0 +1,51 @@
+//! Signing of outgoing API requests
+
+use futures::future::BoxFuture;
+use reqwest::{Client, Request, RequestBuilder, Response};
+
+/// Hook changing every request to the DataDog API before it is sent, e.g. to add SigV4 or
+/// gateway signatures required by an egress proxy
+///
+/// Requests are passed built, so the signature can cover the URL, headers and body. Closures
+/// returning a [`BoxFuture`] are signers.
+///
+/// ```
+/// use futures::future::FutureExt;
+/// use metrics_datadog_exporter::DataDogBuilder;
+/// use reqwest::header::HeaderValue;
+///
+/// let builder = DataDogBuilder::default().request_signer(|mut request: reqwest::Request| {
+///     async move {
+///         let signature = HeaderValue::from_static("signature");
+///         request.headers_mut().insert("x-gateway-signature", signature);
+///         request
+///     }
+///     .boxed()
+/// });
+/// ```
+pub trait DataDogRequestSigner: Send + Sync {
+    /// `request` with its signature added
+    fn sign(&self, request: Request) -> BoxFuture<'_, Request>;
+}
+
+impl<F> DataDogRequestSigner for F
+where
+    F: Fn(Request) -> BoxFuture<'static, Request> + Send + Sync,
+{
+    fn sign(&self, request: Request) -> BoxFuture<'_, Request> {
+        self(request)
+    }
+}
+
+/// Send `request`, signed by `signer` if any
+pub(crate) async fn send(
+    client: &Client,
+    request: RequestBuilder,
+    signer: Option<&dyn DataDogRequestSigner>,
+) -> reqwest::Result<Response> {
+    let Some(signer) = signer else {
+        return request.send().await;
+    };
+    let request = signer.sign(request.build()?).await;
+    client.execute(request).await
+}
@@ -0,0 +1,94 @@
+//! Destinations flushed metrics are written to
+
+use std::sync::Arc;
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::data::DataDogMetric;
+use crate::dogstatsd::DogStatsD;
+use crate::Result;
+
+/// Metrics of a flush submitted with the same API key
+///
+/// A flush is split into one payload per API key set with
+/// [`DataDogBuilder::route_api_keys`](crate::DataDogBuilder::route_api_keys), a single payload
+/// without routing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDogPayload {
+    /// API key the metrics are routed to, none for the default API key
+    pub api_key: Option<String>,
+    /// Metrics, transformed as they are encoded for the API
+    pub metrics: Vec<DataDogMetric>,
+}
+
+impl DataDogPayload {
+    /// Metrics of every payload
+    pub(crate) fn metrics(payloads: &[DataDogPayload]) -> impl Iterator<Item = &DataDogMetric> {
+        payloads.iter().flat_map(|payload| &payload.metrics)
+    }
+}
+
+/// Destination of flushed metrics, e.g. a message queue, an alternative intake or a test double
+///
+/// Sinks receive the payloads of every flush once transformed. The DataDog API, stdout and
+/// DogStatsD are built-in sinks. Errors of a sink fail the flush, after every sink was written
+/// to.
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use futures::future::{BoxFuture, FutureExt};
+/// use metrics_datadog_exporter::data::DataDogSeries;
+/// use metrics_datadog_exporter::{DataDogPayload, DataDogSink, Result};
+///
+/// /// Series as they would be posted to the API
+/// struct Recorded(Mutex<Vec<DataDogSeries>>);
+///
+/// impl DataDogSink for Recorded {
+///     fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+///         let metrics = payloads.iter().flat_map(|payload| payload.metrics.clone());
+///         self.0.lock().unwrap().extend(metrics.flat_map(DataDogSeries::new));
+///         async { Ok(()) }.boxed()
+///     }
+/// }
+/// ```
+pub trait DataDogSink: Send + Sync {
+    /// Write the payloads of a flush
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Shared sinks, e.g. a test double kept to inspect what was written
+impl<T: DataDogSink + ?Sized> DataDogSink for Arc<T> {
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+        (**self).submit(payloads)
+    }
+}
+
+/// Writes metrics to stdout in DataDog JSON format, one line per point
+#[cfg(feature = "stdout")]
+pub(crate) struct StdoutSink;
+
+#[cfg(feature = "stdout")]
+impl DataDogSink for StdoutSink {
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+        let written = DataDogPayload::metrics(payloads)
+            .flat_map(DataDogMetric::to_metric_lines)
+            .try_for_each(|line| {
+                println!("{}", serde_json::to_string(&line)?);
+                Ok(())
+            });
+        async { written }.boxed()
+    }
+}
+
+impl DataDogSink for DogStatsD {
+    fn submit<'a>(&'a self, payloads: &'a [DataDogPayload]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            for payload in payloads {
+                self.write(&payload.metrics).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
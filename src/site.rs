@@ -0,0 +1,71 @@
+//! DataDog sites
+
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+/// DataDog site an organization is hosted on, see the
+/// [site documentation](https://docs.datadoghq.com/getting_started/site/)
+///
+/// Set with [`DataDogBuilder::site`](crate::DataDogBuilder::site), use
+/// [`DataDogBuilder::api_host`](crate::DataDogBuilder::api_host) for proxies and other custom
+/// hosts. Parses from the site domain, as in `DD_SITE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataDogSite {
+    /// `datadoghq.com`
+    #[default]
+    US1,
+    /// `us3.datadoghq.com`
+    US3,
+    /// `us5.datadoghq.com`
+    US5,
+    /// `datadoghq.eu`
+    EU1,
+    /// `ap1.datadoghq.com`
+    AP1,
+    /// `ddog-gov.com`
+    US1Fed,
+}
+
+impl DataDogSite {
+    const ALL: [DataDogSite; 6] = [
+        DataDogSite::US1,
+        DataDogSite::US3,
+        DataDogSite::US5,
+        DataDogSite::EU1,
+        DataDogSite::AP1,
+        DataDogSite::US1Fed,
+    ];
+
+    /// Domain of the site, e.g. `datadoghq.eu`
+    pub fn domain(&self) -> &'static str {
+        match self {
+            DataDogSite::US1 => "datadoghq.com",
+            DataDogSite::US3 => "us3.datadoghq.com",
+            DataDogSite::US5 => "us5.datadoghq.com",
+            DataDogSite::EU1 => "datadoghq.eu",
+            DataDogSite::AP1 => "ap1.datadoghq.com",
+            DataDogSite::US1Fed => "ddog-gov.com",
+        }
+    }
+
+    /// API host of the site, e.g. `https://api.datadoghq.eu/api/v1`
+    pub fn api_host(&self) -> String {
+        format!("https://api.{}/api/v1", self.domain())
+    }
+}
+
+impl FromStr for DataDogSite {
+    type Err = Error;
+
+    /// Site with the domain `site`, fails with [`Error::UnknownSite`] for other domains
+    fn from_str(site: &str) -> Result<Self> {
+        let domain = site.trim().trim_end_matches('/');
+        DataDogSite::ALL
+            .into_iter()
+            .find(|s| s.domain().eq_ignore_ascii_case(domain))
+            .ok_or_else(|| Error::UnknownSite {
+                site: site.to_string(),
+            })
+    }
+}
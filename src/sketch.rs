@@ -0,0 +1,179 @@
+//! Histograms stored as DDSketches
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use metrics::{Histogram, HistogramFn, Key};
+use parking_lot::Mutex;
+
+/// Quantile sketch with a bounded relative error, as used by the DataDog agent
+///
+/// Observations are counted in logarithmically sized bins, so memory grows with the range of the
+/// observations rather than their number
+#[derive(Debug, Clone)]
+pub(crate) struct DDSketch {
+    /// Logarithm of the ratio between consecutive bin bounds
+    ln_gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    /// Bins of the absolute value of negative observations
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl DDSketch {
+    /// Sketch whose quantiles are within `relative_accuracy` of the exact ones, e.g. `0.01`
+    pub(crate) fn new(relative_accuracy: f64) -> Self {
+        let relative_accuracy = relative_accuracy.clamp(f64::EPSILON, 0.5);
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+        DDSketch {
+            ln_gamma: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub(crate) fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        if value > f64::MIN_POSITIVE {
+            *self.positive.entry(self.index(value)).or_default() += 1;
+        } else if value < -f64::MIN_POSITIVE {
+            *self.negative.entry(self.index(-value)).or_default() += 1;
+        } else {
+            self.zeros += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub(crate) fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Smallest observation, [`None`] if there are none
+    pub(crate) fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// Largest observation, [`None`] if there are none
+    pub(crate) fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Estimate of the observation at 1-based `rank` in ascending order, [`None`] if there are
+    /// none
+    pub(crate) fn value_at_rank(&self, rank: u64) -> Option<f64> {
+        let rank = rank.clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.negative.iter().rev() {
+            seen += count;
+            if seen >= rank {
+                return Some((-self.value(*index)).clamp(self.min, self.max));
+            }
+        }
+        seen += self.zeros;
+        if seen >= rank {
+            return Some(0.0);
+        }
+        for (index, count) in &self.positive {
+            seen += count;
+            if seen >= rank {
+                return Some(self.value(*index).clamp(self.min, self.max));
+            }
+        }
+        None
+    }
+
+    fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.ln_gamma).ceil() as i32
+    }
+
+    /// Value of bin `index` with the lowest relative error to the values it holds
+    fn value(&self, index: i32) -> f64 {
+        let gamma = self.ln_gamma.exp();
+        2.0 * (index as f64 * self.ln_gamma).exp() / (1.0 + gamma)
+    }
+}
+
+/// Sketch of a single histogram, recorded into by its [`Histogram`] handles
+struct KeySketch(Mutex<DDSketch>);
+
+impl HistogramFn for KeySketch {
+    fn record(&self, value: f64) {
+        self.0.lock().add(value);
+    }
+}
+
+/// Sketches of the histograms recorded since the last collection, shared by the recorder and
+/// the exporter
+#[derive(Clone)]
+pub(crate) struct SketchStore {
+    relative_accuracy: f64,
+    sketches: Arc<Mutex<HashMap<Key, Arc<KeySketch>>>>,
+}
+
+impl SketchStore {
+    pub(crate) fn new(relative_accuracy: f64) -> Self {
+        SketchStore {
+            relative_accuracy,
+            sketches: Arc::default(),
+        }
+    }
+
+    /// Handle recording into the sketch of `key`
+    pub(crate) fn histogram(&self, key: &Key) -> Histogram {
+        let sketch = self
+            .sketches
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(KeySketch(Mutex::new(DDSketch::new(self.relative_accuracy))))
+            })
+            .clone();
+        Histogram::from_arc(sketch)
+    }
+
+    /// Remove and return the sketches of keys matching `filter`
+    pub(crate) fn take(&self, filter: impl Fn(&Key) -> bool) -> Vec<(Key, DDSketch)> {
+        let mut sketches = self.sketches.lock();
+        let taken = sketches
+            .keys()
+            .filter(|k| filter(k))
+            .cloned()
+            .collect::<Vec<_>>();
+        taken
+            .into_iter()
+            .filter_map(|key| {
+                let sketch = sketches.remove(&key)?;
+                let sketch = sketch.0.lock().clone();
+                Some((key, sketch))
+            })
+            .collect()
+    }
+
+    /// Drop every sketch
+    pub(crate) fn clear(&self) {
+        self.sketches.lock().clear();
+    }
+
+    /// Number of histograms recorded since the last collection
+    pub(crate) fn len(&self) -> usize {
+        self.sketches.lock().len()
+    }
+}
@@ -0,0 +1,45 @@
+//! Custom metric kinds collected alongside the registry
+
+use metrics::Key;
+
+use crate::data::DataDogMetric;
+
+/// Source of metrics stored outside the registry, e.g. a custom summary type, collected on every
+/// flush alongside counters, gauges and histograms
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use metrics::Key;
+/// use metrics_datadog_exporter::{
+///     DataDogMetric, DataDogMetricSource, DataDogMetricType, DataDogMetricValue,
+/// };
+///
+/// struct Summary(Mutex<Vec<f64>>);
+///
+/// impl DataDogMetricSource for Summary {
+///     fn collect(&self, filter: &dyn Fn(&Key) -> bool) -> Vec<DataDogMetric> {
+///         if !filter(&Key::from_static_name("summary.max")) {
+///             return vec![];
+///         }
+///         let values = std::mem::take(&mut *self.0.lock().unwrap());
+///         let Some(max) = values.into_iter().reduce(f64::max) else {
+///             return vec![];
+///         };
+///         vec![DataDogMetric {
+///             metric: "summary.max".to_string(),
+///             metric_type: DataDogMetricType::Gauge,
+///             points: vec![DataDogMetricValue::Float(max)],
+///             timestamp: chrono::Utc::now().timestamp(),
+///             tags: vec![],
+///         }]
+///     }
+/// }
+/// ```
+pub trait DataDogMetricSource: Send + Sync {
+    /// Metrics to export with this flush
+    ///
+    /// Partial flushes pass a `filter` rejecting the keys they leave for later, sources should
+    /// then keep the rejected values. Global tags are added to the returned metrics.
+    fn collect(&self, filter: &dyn Fn(&Key) -> bool) -> Vec<DataDogMetric>;
+}
@@ -0,0 +1,277 @@
+//! Exporter status
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::memory::DataDogMemoryPressure;
+use crate::{Error, Result};
+
+/// Health of a [`DataDogExporter`](crate::DataDogExporter)
+#[derive(Debug, Clone, Default)]
+pub struct DataDogStatus {
+    /// Time of the last successful flush
+    pub last_success: Option<DateTime<Utc>>,
+    /// Time of the last failed flush
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Error returned by the last failed flush
+    pub last_error: Option<String>,
+    /// Number of failed flushes since the last successful one
+    pub consecutive_failures: u64,
+    /// The API key provider failed, or the API rejected the API key as forbidden, since the last
+    /// successful request
+    pub api_misconfigured: bool,
+    /// Health of each API host, primary first
+    pub endpoints: Vec<DataDogEndpointStatus>,
+    /// Number of series dropped for exceeding the series budget, or for being low priority while
+    /// rate limited
+    pub dropped_series: u64,
+    /// Number of flushes sent while a recently used connection was likely still open
+    pub warm_flushes: u64,
+    /// Number of flushes likely needing a new connection
+    pub cold_flushes: u64,
+    /// Number of times compression failed and payloads were sent uncompressed instead
+    pub compression_fallbacks: u64,
+    /// Number of requests retried after a transient failure
+    pub retries: u64,
+    /// Number of payloads held back by the DataDog rate limit, resubmitted by later flushes
+    pub deferred_payloads: usize,
+    /// Number of histogram observations discarded by
+    /// [`DataDogBuilder::histogram_sample_limit`](crate::DataDogBuilder::histogram_sample_limit)
+    pub discarded_samples: u64,
+    /// Memory held at the last flush, if
+    /// [`DataDogBuilder::memory_budget`](crate::DataDogBuilder::memory_budget) is set
+    pub memory: Option<DataDogMemoryPressure>,
+    /// Stage timings of the last flush
+    pub last_flush: Option<DataDogFlushStats>,
+    /// Delivery totals since the exporter was built
+    pub delivery: DataDogDeliveryReport,
+    /// Health of each destination set with
+    /// [`DataDogBuilder::destination`](crate::DataDogBuilder::destination), whose failures don't
+    /// fail the flush
+    pub destinations: Vec<DataDogDestinationStatus>,
+}
+
+/// Health of a destination, submitted to by an exporter of its own
+#[derive(Debug, Clone)]
+pub struct DataDogDestinationStatus {
+    /// API host of the destination
+    pub api_host: String,
+    /// Status of the exporter submitting to the destination: failed submissions, retries,
+    /// payloads deferred while rate limited and the health of its API hosts
+    pub status: DataDogStatus,
+}
+
+pub(crate) type DeliveryReportCallback = Arc<dyn Fn(&DataDogDeliveryReport) + Send + Sync>;
+
+/// Duration of each stage of a flush
+///
+/// A slow flush spending most of its time in [`send`](Self::send) is network-bound, one
+/// spending it in the other stages is CPU-bound
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DataDogFlushStats {
+    /// Collecting metrics from the registry
+    pub collect: Duration,
+    /// Tagging, clock skew correction and series budget
+    pub transform: Duration,
+    /// Serializing and compressing request payloads, which are streamed together
+    pub encode: Duration,
+    /// Sending requests to the DataDog API
+    pub send: Duration,
+    /// Number of metrics written
+    pub metrics: usize,
+    /// Number of request payloads
+    pub payloads: usize,
+    /// Number of request payloads compressed with gzip, the others are uncompressed or zstd
+    pub gzip_payloads: usize,
+    /// Number of request payloads compressed with zstd
+    pub zstd_payloads: usize,
+    /// Number of points written
+    pub points: usize,
+    /// Size of the payloads accepted by the API
+    pub bytes: usize,
+}
+
+/// Accounting of metrics delivery over the lifetime of an exporter, reported on
+/// [`shutdown`](crate::DataDogExporter::shutdown)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DataDogDeliveryReport {
+    /// Number of flushes
+    pub flushes: u64,
+    /// Number of failed flushes
+    pub failed_flushes: u64,
+    /// Number of points written
+    pub points: u64,
+    /// Size of the payloads accepted by the API
+    pub bytes: u64,
+    /// Failed requests by kind, e.g. `status 500` or `timeout`, other failed flushes as `other`
+    pub errors: BTreeMap<String, u64>,
+    /// Number of series dropped for exceeding the series budget, or for being low priority while
+    /// rate limited
+    pub dropped_series: u64,
+    /// Number of payloads still held back by the DataDog rate limit
+    pub deferred_payloads: usize,
+}
+
+/// Health of a single API host
+#[derive(Debug, Clone)]
+pub struct DataDogEndpointStatus {
+    /// API host
+    pub host: String,
+    /// Whether the last request to this host succeeded
+    pub healthy: bool,
+    /// Number of failed requests since the last successful one
+    pub consecutive_failures: u64,
+    /// Error returned by the last failed request
+    pub last_error: Option<String>,
+    /// Number of requests sent to this host
+    pub requests: u64,
+    /// Number of concurrent requests allowed to this host, see
+    /// [`DataDogBuilder::request_concurrency`](crate::DataDogBuilder::request_concurrency)
+    pub concurrency_limit: Option<usize>,
+    /// Time requests spent waiting for the concurrency limit
+    pub slot_wait: Duration,
+}
+
+impl DataDogStatus {
+    pub(crate) fn record_flush(&mut self, result: &Result<()>, stats: &DataDogFlushStats) {
+        self.delivery.flushes += 1;
+        self.delivery.points += stats.points as u64;
+        self.delivery.bytes += stats.bytes as u64;
+        match result {
+            Ok(()) => {
+                self.last_success = Some(Utc::now());
+                self.consecutive_failures = 0;
+            }
+            Err(e) => {
+                self.last_failure = Some(Utc::now());
+                self.last_error = Some(e.to_string());
+                self.consecutive_failures += 1;
+                self.delivery.failed_flushes += 1;
+                self.delivery.record_error(e);
+            }
+        }
+        self.delivery.dropped_series = self.dropped_series;
+        self.delivery.deferred_payloads = self.deferred_payloads;
+    }
+}
+
+impl DataDogDeliveryReport {
+    fn record_error(&mut self, error: &Error) {
+        let Error::FlushFailed(errors) = error else {
+            *self.errors.entry("other".to_string()).or_default() += 1;
+            return;
+        };
+        for (kind, failure) in &errors.failures {
+            *self.errors.entry(kind.clone()).or_default() += failure.count as u64;
+        }
+    }
+}
+
+impl DataDogEndpointStatus {
+    pub(crate) fn new(host: &str) -> Self {
+        DataDogEndpointStatus {
+            host: host.to_string(),
+            healthy: true,
+            consecutive_failures: 0,
+            last_error: None,
+            requests: 0,
+            concurrency_limit: None,
+            slot_wait: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn record_request<T>(
+        &mut self,
+        result: &Result<T, reqwest::Error>,
+        slot_wait: Duration,
+    ) {
+        self.requests += 1;
+        self.slot_wait += slot_wait;
+        match result {
+            Ok(_) => {
+                self.healthy = true;
+                self.consecutive_failures = 0;
+            }
+            Err(e) => {
+                self.healthy = false;
+                self.last_error = Some(e.to_string());
+                self.consecutive_failures += 1;
+            }
+        }
+    }
+}
+
+/// Failed requests of a single flush, grouped by kind of failure
+#[derive(Debug, Clone, Default)]
+pub struct DataDogFlushErrors {
+    /// Number of requests sent during the flush
+    pub requests: usize,
+    /// Failures keyed by kind, e.g. `status 500`, `timeout` or `rejected 200`
+    pub failures: BTreeMap<String, DataDogFlushFailure>,
+    /// Request IDs of the failed requests
+    pub request_ids: Vec<String>,
+}
+
+/// Failed requests of a single kind
+#[derive(Debug, Clone)]
+pub struct DataDogFlushFailure {
+    /// Number of failed requests
+    pub count: usize,
+    /// Error of the first failed request
+    pub first_error: String,
+}
+
+impl DataDogFlushErrors {
+    /// Whether no request failed
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Total number of failed requests
+    pub fn failed(&self) -> usize {
+        self.failures.values().map(|failure| failure.count).sum()
+    }
+
+    pub(crate) fn record(&mut self, error: &Error) {
+        let (kind, request_id) = match error {
+            Error::RequestFailed { request_id, source } => {
+                let kind = match source.status() {
+                    Some(status) => format!("status {}", status.as_u16()),
+                    None if source.is_timeout() => "timeout".to_string(),
+                    None if source.is_connect() => "connect".to_string(),
+                    None => "request".to_string(),
+                };
+                (kind, Some(request_id))
+            }
+            Error::IntakeRejected {
+                request_id, status, ..
+            } => (format!("rejected {}", status.as_u16()), Some(request_id)),
+            _ => ("other".to_string(), None),
+        };
+
+        self.failures
+            .entry(kind)
+            .and_modify(|failure| failure.count += 1)
+            .or_insert_with(|| DataDogFlushFailure {
+                count: 1,
+                first_error: error.to_string(),
+            });
+        self.request_ids.extend(request_id.cloned());
+    }
+}
+
+impl fmt::Display for DataDogFlushErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} requests failed", self.failed(), self.requests)?;
+        for (index, (kind, failure)) in self.failures.iter().enumerate() {
+            let separator = if index == 0 { ": " } else { ", " };
+            write!(f, "{separator}{kind} ({})", failure.count)?;
+        }
+        Ok(())
+    }
+}
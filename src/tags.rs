@@ -0,0 +1,87 @@
+//! Tags with typed helpers and format validation
+
+use crate::naming::tag_violation;
+use crate::{Error, Result};
+
+/// Tags sent with metrics, checked when building so typos like `env=prod` fail at startup
+///
+/// ```
+/// # use metrics_datadog_exporter::{DataDogBuilder, DataDogTags};
+/// let builder = DataDogBuilder::default().tags(
+///     DataDogTags::new()
+///         .env("prod")
+///         .service("checkout")
+///         .region("eu-west-1")
+///         .custom("team", "payments"),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataDogTags(Vec<(String, String)>);
+
+impl DataDogTags {
+    /// No tags
+    pub fn new() -> Self {
+        DataDogTags::default()
+    }
+
+    /// Add the `env` tag used by DataDog unified service tagging
+    #[must_use]
+    pub fn env(self, env: impl Into<String>) -> Self {
+        self.custom("env", env)
+    }
+
+    /// Add the `service` tag used by DataDog unified service tagging
+    #[must_use]
+    pub fn service(self, service: impl Into<String>) -> Self {
+        self.custom("service", service)
+    }
+
+    /// Add the `version` tag used by DataDog unified service tagging
+    #[must_use]
+    pub fn version(self, version: impl Into<String>) -> Self {
+        self.custom("version", version)
+    }
+
+    /// Add the `region` tag
+    #[must_use]
+    pub fn region(self, region: impl Into<String>) -> Self {
+        self.custom("region", region)
+    }
+
+    /// Add the tag `key:value`
+    #[must_use]
+    pub fn custom(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.0.push((key.into(), value.into()));
+        self
+    }
+
+    /// Fails with [`Error::InvalidTag`] on the first tag DataDog would reject or mangle
+    pub fn validate(&self) -> Result<()> {
+        self.0
+            .iter()
+            .try_for_each(|(key, value)| validate(key, value))
+    }
+
+    /// Tags as key and value pairs
+    pub fn into_pairs(self) -> Vec<(String, String)> {
+        self.0
+    }
+}
+
+impl From<Vec<(String, String)>> for DataDogTags {
+    fn from(tags: Vec<(String, String)>) -> Self {
+        DataDogTags(tags)
+    }
+}
+
+/// Check the tag `key:value`, after resolving placeholders, against the rules of
+/// [`tag_violation`]
+pub(crate) fn validate(key: &str, value: &str) -> Result<()> {
+    match tag_violation(key, Some(value)) {
+        Some(reason) => Err(Error::InvalidTag {
+            tag: format!("{key}:{value}"),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::{Error, Result};
+
+/// Resolve placeholders in a tag value
+///
+/// `{env:NAME}` is replaced by the environment variable `NAME` and `{name}` by the context value
+/// `name`. Use `{{` and `}}` for literal braces.
+pub(crate) fn resolve(value: &str, context: &HashMap<String, String>) -> Result<String> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(index) = rest.find(['{', '}']) {
+        resolved.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if let Some(stripped) = rest.strip_prefix("{{") {
+            resolved.push('{');
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("}}") {
+            resolved.push('}');
+            rest = stripped;
+        } else if let Some(end) = rest.find('}').filter(|_| rest.starts_with('{')) {
+            let name = &rest[1..end];
+            let substitution = match name.strip_prefix("env:") {
+                Some(var) => env::var(var).ok(),
+                None => context.get(name).cloned(),
+            };
+            resolved.push_str(&substitution.ok_or_else(|| Error::UnresolvedTag {
+                value: value.to_string(),
+                placeholder: name.to_string(),
+            })?);
+            rest = &rest[end + 1..];
+        } else {
+            return Err(Error::UnresolvedTag {
+                value: value.to_string(),
+                placeholder: rest.to_string(),
+            });
+        }
+    }
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
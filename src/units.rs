@@ -0,0 +1,74 @@
+//! Unit names
+
+use metrics::Unit;
+
+/// DataDog unit of a metric
+///
+/// See <https://docs.datadoghq.com/metrics/units/> for the units DataDog understands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataDogUnit {
+    /// Unit known to [`metrics`]
+    Known(Unit),
+    /// Any other DataDog unit, optionally per another unit (e.g. `request` per `second`)
+    Custom {
+        /// Unit name
+        unit: String,
+        /// Unit name of the denominator
+        per_unit: Option<String>,
+    },
+}
+
+impl DataDogUnit {
+    /// DataDog unit name
+    pub fn unit(&self) -> &str {
+        match self {
+            DataDogUnit::Known(unit) => datadog_unit(unit).0,
+            DataDogUnit::Custom { unit, .. } => unit,
+        }
+    }
+
+    /// DataDog unit name of the denominator, if any
+    pub fn per_unit(&self) -> Option<&str> {
+        match self {
+            DataDogUnit::Known(unit) => datadog_unit(unit).1,
+            DataDogUnit::Custom { per_unit, .. } => per_unit.as_deref(),
+        }
+    }
+
+    /// Value of the `unit:` tag, e.g. `byte` or `bit_per_second`
+    pub fn tag_value(&self) -> String {
+        match self.per_unit() {
+            Some(per_unit) => format!("{}_per_{}", self.unit(), per_unit),
+            None => self.unit().to_string(),
+        }
+    }
+}
+
+impl From<Unit> for DataDogUnit {
+    fn from(unit: Unit) -> Self {
+        DataDogUnit::Known(unit)
+    }
+}
+
+/// DataDog unit and per-unit names of a [`Unit`]
+pub fn datadog_unit(unit: &Unit) -> (&'static str, Option<&'static str>) {
+    match unit {
+        Unit::Count => ("unit", None),
+        Unit::Percent => ("percent", None),
+        Unit::Seconds => ("second", None),
+        Unit::Milliseconds => ("millisecond", None),
+        Unit::Microseconds => ("microsecond", None),
+        Unit::Nanoseconds => ("nanosecond", None),
+        Unit::Tebibytes => ("tebibyte", None),
+        Unit::Gigibytes => ("gibibyte", None),
+        Unit::Mebibytes => ("mebibyte", None),
+        Unit::Kibibytes => ("kibibyte", None),
+        Unit::Bytes => ("byte", None),
+        Unit::TerabitsPerSecond => ("terabit", Some("second")),
+        Unit::GigabitsPerSecond => ("gigabit", Some("second")),
+        Unit::MegabitsPerSecond => ("megabit", Some("second")),
+        Unit::KilobitsPerSecond => ("kilobit", Some("second")),
+        Unit::BitsPerSecond => ("bit", Some("second")),
+        Unit::CountPerSecond => ("unit", Some("second")),
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use metrics::Key;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use crate::naming::{name_violation, tag_violation};
+
+/// Invalid keys remembered so they are warned about once, forgotten all at once past this
+const MAX_WARNED_KEYS: usize = 1024;
+
+/// What to do when a metric is registered with a name or labels DataDog would reject or rewrite
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DataDogValidation {
+    /// Don't validate
+    Off,
+    /// Log a warning the first time each key is registered
+    Warn,
+    /// Panic at the registration call site
+    Panic,
+}
+
+impl Default for DataDogValidation {
+    /// [`Warn`](DataDogValidation::Warn) with debug assertions, [`Off`](DataDogValidation::Off)
+    /// otherwise
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            DataDogValidation::Warn
+        } else {
+            DataDogValidation::Off
+        }
+    }
+}
+
+/// Hashes of the invalid keys already warned about
+#[derive(Default)]
+pub(crate) struct WarnedKeys(Mutex<HashSet<u64>>);
+
+impl WarnedKeys {
+    /// Remember `key`, returning whether it wasn't warned about yet
+    fn first_warning(&self, key: &Key) -> bool {
+        let mut warned = self.0.lock();
+        if warned.contains(&key.get_hash()) {
+            return false;
+        }
+        if warned.len() >= MAX_WARNED_KEYS {
+            warned.clear();
+        }
+        warned.insert(key.get_hash())
+    }
+}
+
+impl DataDogValidation {
+    /// Validate `key`, warning about invalid keys not in `warned` yet
+    pub(crate) fn check(&self, key: &Key, warned: &WarnedKeys) {
+        if *self == DataDogValidation::Off {
+            return;
+        }
+        let mut violations = std::iter::once(name_violation(key.name()).map(str::to_string))
+            .chain(key.labels().map(|l| {
+                tag_violation(l.key(), Some(l.value()))
+                    .map(|reason| format!("tag `{}`: {reason}", l.key()))
+            }))
+            .flatten()
+            .peekable();
+        if violations.peek().is_none()
+            || (*self == DataDogValidation::Warn && !warned.first_warning(key))
+        {
+            return;
+        }
+        for violation in violations {
+            match self {
+                DataDogValidation::Off => {}
+                DataDogValidation::Warn => {
+                    warn!(metric = key.name(), "Invalid metric: {violation}")
+                }
+                DataDogValidation::Panic => panic!("Invalid metric `{}`: {violation}", key.name()),
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::Result;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Dedicated named OS thread running blocking work, so profiles attribute it to the exporter
+pub(crate) struct Worker {
+    jobs: Mutex<Sender<Job>>,
+}
+
+impl Worker {
+    pub(crate) fn new(name: &str) -> io::Result<Self> {
+        let (jobs, queue) = channel::<Job>();
+        thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                for job in queue {
+                    // A panicking job fails its own caller, not the ones queued after it
+                    let _ = catch_unwind(AssertUnwindSafe(job));
+                }
+            })?;
+        Ok(Worker {
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    /// Run `f` on the worker thread and wait for its result
+    pub(crate) async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result, receiver) = oneshot::channel();
+        self.jobs
+            .lock()
+            .send(Box::new(move || {
+                let _ = result.send(f());
+            }))
+            .map_err(|_| io::Error::other("worker thread stopped"))?;
+        Ok(receiver
+            .await
+            .map_err(|_| io::Error::other("worker job panicked"))?)
+    }
+}
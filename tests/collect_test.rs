@@ -1,17 +1,25 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
 use anyhow::Result;
-use metrics::{counter, gauge, histogram};
+use metrics::{counter, gauge, histogram, with_local_recorder, Key};
+use metrics_datadog_exporter::data::DataDogSeries;
 use metrics_datadog_exporter::{
-    DataDogBuilder, DataDogMetric, DataDogMetricType, DataDogMetricValue,
+    DataDogBuilder, DataDogCounterTemporality, DataDogEmptyFlush, DataDogFlushViolation,
+    DataDogGaugeAggregation, DataDogHistogramAggregate, DataDogMetric, DataDogMetricSource,
+    DataDogMetricType, DataDogMetricValue, DataDogRegistryStats, DataDogSeriesType,
+    DataDogSeriesTypePolicy,
 };
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[test]
 fn collect_test() -> Result<()> {
     let metrics = DataDogBuilder::default().build()?.install()?;
-    counter!("this.counter", 123, "tag2" => "value2");
-    gauge!("this.gauge", 234.0, "tag3" => "value3");
-    histogram!("this.histogram", 345.0, "tag4" => "value5");
-    histogram!("this.histogram", 456.0, "tag4" => "value5");
+    counter!("this.counter", "tag2" => "value2").increment(123);
+    gauge!("this.gauge", "tag3" => "value3").set(234.0);
+    histogram!("this.histogram", "tag4" => "value5").record(345.0);
+    histogram!("this.histogram", "tag4" => "value5").record(456.0);
     let collected = metrics
         .collect()
         .into_iter()
@@ -27,3 +35,501 @@ fn collect_test() -> Result<()> {
     assert_eq!(histogram.points.len(), 2);
     Ok(())
 }
+
+#[test]
+fn series_type_policy_test() -> Result<()> {
+    let metric = DataDogMetric {
+        metric: "this.counter".to_string(),
+        metric_type: DataDogMetricType::Count,
+        points: vec![DataDogMetricValue::Unsigned(1)],
+        timestamp: 0,
+        tags: vec![],
+    };
+    let policy = DataDogSeriesTypePolicy {
+        counter: DataDogSeriesType::Rate,
+        ..DataDogSeriesTypePolicy::default()
+    };
+    let series = DataDogSeries::with_policy(metric, &policy);
+    assert_eq!(series[0].metric_type, DataDogSeriesType::Rate);
+    assert_eq!(serde_json::to_value(&series[0])?["type"], "rate");
+    Ok(())
+}
+
+#[test]
+fn tag_cache_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric", "tag" => "value").increment(1)
+    });
+    let first = metrics.handle.collect();
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric", "tag" => "value").increment(1)
+    });
+    let second = metrics.handle.collect();
+
+    assert_eq!(first[0].tags, vec!["tag:value".to_string()]);
+    assert_eq!(second[0].tags, first[0].tags);
+    Ok(())
+}
+
+#[test]
+fn gauge_aggregation_test() {
+    let values = [3.0, 1.0, 2.0];
+    assert_eq!(DataDogGaugeAggregation::Last.aggregate(&values), Some(2.0));
+    assert_eq!(DataDogGaugeAggregation::Min.aggregate(&values), Some(1.0));
+    assert_eq!(DataDogGaugeAggregation::Max.aggregate(&values), Some(3.0));
+    assert_eq!(DataDogGaugeAggregation::Mean.aggregate(&values), Some(2.0));
+    assert_eq!(DataDogGaugeAggregation::Sum.aggregate(&values), Some(6.0));
+    assert_eq!(DataDogGaugeAggregation::Sum.aggregate(&[]), None);
+}
+
+#[tokio::test]
+async fn flush_matching_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().write_to_stdout(false).build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("batch.rows").increment(1);
+        counter!("requests").increment(1);
+    });
+    metrics
+        .handle
+        .flush_matching(|key| key.name().starts_with("batch."))
+        .await?;
+
+    let remaining = metrics.handle.collect();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].metric, "requests");
+    Ok(())
+}
+
+#[test]
+fn reset_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    metrics.handle.reset();
+
+    assert!(metrics.handle.collect().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_metrics_test() -> Result<()> {
+    let exporter = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .build_exporter()?;
+
+    let metric = DataDogMetric {
+        metric: "injected".to_string(),
+        metric_type: DataDogMetricType::Gauge,
+        points: vec![DataDogMetricValue::Float(1.0)],
+        timestamp: 0,
+        tags: vec![],
+    };
+    exporter.export_metrics(vec![metric]).await?;
+
+    assert!(exporter.status().last_success.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn compression_thread_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .compression_thread(true)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    metrics.flush().await?;
+
+    assert!(metrics.handle.collect().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn empty_flush_test() -> Result<()> {
+    let stats = Arc::new(Mutex::new(vec![]));
+    let recorded = stats.clone();
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .on_empty_flush(DataDogEmptyFlush::callback(move |stats| {
+            recorded.lock().unwrap().push(*stats)
+        }))
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(1));
+    for _ in 0..2 {
+        metrics
+            .handle
+            .flush_matching(|key| key.name().starts_with("batch."))
+            .await?;
+    }
+
+    assert_eq!(
+        *stats.lock().unwrap(),
+        [
+            DataDogRegistryStats {
+                counters: 1,
+                gauges: 0,
+                histograms: 0,
+                consecutive_empty_flushes: 1,
+            },
+            DataDogRegistryStats {
+                counters: 1,
+                gauges: 0,
+                histograms: 0,
+                consecutive_empty_flushes: 2,
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn shadow_test() -> Result<()> {
+    let shadowed = Arc::new(Mutex::new(vec![]));
+    let recorded = shadowed.clone();
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .shadow(50.0, move |metrics| {
+            recorded.lock().unwrap().push(metrics.len())
+        })
+        .build()?;
+
+    for _ in 0..4 {
+        with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+        metrics.flush().await?;
+    }
+
+    assert_eq!(*shadowed.lock().unwrap(), [1, 1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn recent_flushes_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .recent_flushes(2)
+        .build()?;
+
+    for name in ["first", "second", "third"] {
+        with_local_recorder(&metrics.recorder, || counter!(name).increment(1));
+        metrics.flush().await?;
+    }
+
+    let recent = metrics
+        .handle
+        .recent_flushes()
+        .into_iter()
+        .map(|flush| flush.metrics[0].metric.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(recent, ["second", "third"]);
+    Ok(())
+}
+
+#[test]
+fn cumulative_counter_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .counter_temporality(DataDogCounterTemporality::Cumulative)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(2));
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].metric_type, DataDogMetricType::Gauge);
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Unsigned(2)]);
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(3));
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Unsigned(5)]);
+
+    // Reported on every flush, even without increments
+    assert_eq!(metrics.handle.collect().len(), 1);
+    Ok(())
+}
+
+/// Source reporting the running totals it is given, one per flush
+struct Totals(Mutex<Vec<u64>>);
+
+impl DataDogMetricSource for Totals {
+    fn collect(&self, _: &dyn Fn(&Key) -> bool) -> Vec<DataDogMetric> {
+        let total = self.0.lock().unwrap().remove(0);
+        vec![DataDogMetric {
+            metric: "requests".to_string(),
+            metric_type: DataDogMetricType::Count,
+            points: vec![DataDogMetricValue::Unsigned(total)],
+            timestamp: 0,
+            tags: vec![],
+        }]
+    }
+}
+
+#[test]
+fn flush_assertions_test() -> Result<()> {
+    let violations = Arc::new(Mutex::new(vec![]));
+    let reported = violations.clone();
+    let metrics = DataDogBuilder::default()
+        .counter_temporality(DataDogCounterTemporality::Cumulative)
+        .metric_source(Totals(Mutex::new(vec![5, 3])))
+        .gauge_bounds("ratio".to_string(), 0.0..=1.0)
+        .flush_assertions(move |violation| reported.lock().unwrap().push(violation.clone()))
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("recorded").absolute(5);
+        gauge!("ratio").set(0.5);
+    });
+    metrics.handle.collect();
+    assert!(violations.lock().unwrap().is_empty());
+
+    // Recorded counters keep their highest value
+    with_local_recorder(&metrics.recorder, || {
+        counter!("recorded").absolute(3);
+        gauge!("ratio").set(1.5);
+    });
+    metrics.handle.collect();
+    let violations = violations.lock().unwrap();
+    assert_eq!(violations.len(), 2);
+    assert!(matches!(
+        &violations[0],
+        DataDogFlushViolation::GaugeOutOfBounds { metric, value, .. }
+            if metric == "ratio" && *value == 1.5
+    ));
+    assert!(matches!(
+        &violations[1],
+        DataDogFlushViolation::CounterDecreased { metric, previous: 5, current: 3, .. }
+            if metric == "requests"
+    ));
+    Ok(())
+}
+
+#[test]
+fn preserve_gauges_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().preserve_gauges(true).build()?;
+
+    with_local_recorder(&metrics.recorder, || gauge!("connections").set(4.0));
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Float(4.0)]);
+
+    // Still reported without being set again
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Float(4.0)]);
+
+    with_local_recorder(&metrics.recorder, || gauge!("connections").set(2.0));
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Float(2.0)]);
+    Ok(())
+}
+
+#[test]
+fn idle_timeout_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .preserve_gauges(true)
+        .idle_timeout(Duration::from_millis(50))
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || gauge!("tenant.connections").set(4.0));
+    assert_eq!(metrics.handle.collect().len(), 1);
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(metrics.handle.collect().is_empty());
+
+    with_local_recorder(&metrics.recorder, || gauge!("tenant.connections").set(4.0));
+    assert_eq!(metrics.handle.collect().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn idle_timeout_touched_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .preserve_gauges(true)
+        .idle_timeout(Duration::from_millis(50))
+        .build()?;
+
+    // Set to the same value before every flush, the gauge never goes idle
+    for _ in 0..4 {
+        with_local_recorder(&metrics.recorder, || gauge!("pool.size").set(8.0));
+        assert_eq!(metrics.handle.collect().len(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+#[test]
+fn idle_timeout_held_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .preserve_gauges(true)
+        .idle_timeout(Duration::from_millis(50))
+        .build()?;
+
+    let depth = with_local_recorder(&metrics.recorder, || gauge!("queue.depth"));
+    depth.set(1.0);
+    assert_eq!(metrics.handle.collect().len(), 1);
+
+    // Held by the application, the idle gauge is kept so the next update isn't lost
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(metrics.handle.collect().len(), 1);
+    depth.set(2.0);
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].points, vec![DataDogMetricValue::Float(2.0)]);
+    Ok(())
+}
+
+#[test]
+fn histogram_aggregates_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .histogram_aggregates(vec![
+            DataDogHistogramAggregate::Count,
+            DataDogHistogramAggregate::Sum,
+            DataDogHistogramAggregate::Min,
+            DataDogHistogramAggregate::Max,
+            DataDogHistogramAggregate::Avg,
+            DataDogHistogramAggregate::Percentile(50.0),
+            DataDogHistogramAggregate::Percentile(99.9),
+        ])
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        for value in 1..=10 {
+            histogram!("latency").record(value as f64);
+        }
+    });
+    let collected = metrics
+        .handle
+        .collect()
+        .into_iter()
+        .map(|m| (m.metric, m.metric_type, m.points))
+        .collect::<Vec<_>>();
+    let gauge = |name: &str, value| {
+        (
+            name.to_string(),
+            DataDogMetricType::Gauge,
+            vec![DataDogMetricValue::Float(value)],
+        )
+    };
+    assert_eq!(
+        collected,
+        vec![
+            (
+                "latency.count".to_string(),
+                DataDogMetricType::Count,
+                vec![DataDogMetricValue::Unsigned(10)]
+            ),
+            gauge("latency.sum", 55.0),
+            gauge("latency.min", 1.0),
+            gauge("latency.max", 10.0),
+            gauge("latency.avg", 5.5),
+            gauge("latency.p50", 5.0),
+            gauge("latency.p999", 10.0),
+        ]
+    );
+
+    // No observations, no aggregates
+    assert!(metrics.handle.collect().is_empty());
+    Ok(())
+}
+
+#[test]
+fn histogram_sketches_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().histogram_sketches(0.01).build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        for value in 1..=1000 {
+            histogram!("latency").record(value as f64);
+        }
+    });
+    let collected = metrics
+        .handle
+        .collect()
+        .into_iter()
+        .map(|m| (m.metric, m.points[0].as_f64()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(collected.len(), 5);
+    assert_eq!(collected["latency.count"], 1000.0);
+    assert_eq!(collected["latency.avg"], 500.5);
+    assert_eq!(collected["latency.max"], 1000.0);
+    // Quantiles are within the relative accuracy
+    assert!((collected["latency.p50"] - 500.0).abs() <= 5.0);
+    assert!((collected["latency.p95"] - 950.0).abs() <= 9.5);
+
+    assert!(metrics.handle.collect().is_empty());
+    Ok(())
+}
+
+#[test]
+fn histogram_sample_limit_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .histogram_sample_limit(10)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        for value in 1..=100 {
+            histogram!("latency").record(value as f64);
+        }
+    });
+    let collected = metrics.handle.collect();
+    assert_eq!(collected.len(), 1);
+    assert_eq!(collected[0].points.len(), 10);
+    assert!(collected[0]
+        .points
+        .iter()
+        .all(|point| (1.0..=100.0).contains(&point.as_f64())));
+    assert_eq!(metrics.handle.status().discarded_samples, 90);
+    Ok(())
+}
+
+#[test]
+fn namespace_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .namespace("myapp".to_string())
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("http.requests").increment(1);
+        gauge!("datadog.exporter.queue").set(1.0);
+    });
+    let mut names = metrics
+        .handle
+        .collect()
+        .into_iter()
+        .map(|m| m.metric)
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["datadog.exporter.queue", "myapp.http.requests"]);
+    Ok(())
+}
+
+struct Uptime;
+
+impl DataDogMetricSource for Uptime {
+    fn collect(&self, filter: &dyn Fn(&Key) -> bool) -> Vec<DataDogMetric> {
+        if !filter(&Key::from_static_name("uptime")) {
+            return vec![];
+        }
+        vec![DataDogMetric {
+            metric: "uptime".to_string(),
+            metric_type: DataDogMetricType::Gauge,
+            points: vec![DataDogMetricValue::Float(60.0)],
+            timestamp: 0,
+            tags: vec!["source:custom".to_string()],
+        }]
+    }
+}
+
+#[test]
+fn metric_source_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .tags(vec![("env".to_string(), "test".to_string())])
+        .metric_source(Uptime)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(1));
+    let collected = metrics.handle.collect();
+    assert_eq!(collected.len(), 2);
+    let uptime = collected.iter().find(|m| m.metric == "uptime").unwrap();
+    assert_eq!(
+        uptime.tags,
+        vec!["env:test".to_string(), "source:custom".to_string()]
+    );
+    Ok(())
+}
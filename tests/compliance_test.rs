@@ -0,0 +1,52 @@
+#![cfg(all(feature = "recorder", feature = "compliance"))]
+
+use anyhow::Result;
+use httpmock::Method::POST;
+use httpmock::MockServer;
+use metrics::{counter, histogram, with_local_recorder};
+use metrics_datadog_exporter::{compliance, DataDogBuilder, Error};
+
+#[tokio::test]
+async fn exported_payload_compliance_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .tags(vec![("tag".to_string(), "value".to_string())])
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("counter", "key" => "value").increment(1);
+        for i in 0..10 {
+            histogram!("histogram").record(i as f64);
+        }
+    });
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/series")
+            .matches(|req| compliance::validate(req.body.as_ref().unwrap(), true).is_ok());
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert();
+    Ok(())
+}
+
+#[test]
+fn non_compliant_payload_test() {
+    let payload = br#"{"series":[{"metric":"","points":[[1, 1.0, 2.0]],"type":"histogram"}]}"#;
+    let Err(Error::NonCompliantPayload { violations }) = compliance::validate(payload, false)
+    else {
+        panic!("Expected payload to be rejected");
+    };
+    assert_eq!(violations.len(), 3);
+
+    assert!(compliance::validate(b"[]", false).is_err());
+
+    // Distributions have their own intake
+    let payload = br#"{"series":[{"metric":"latency","points":[[1, 1.0]],"type":"distribution"}]}"#;
+    assert!(compliance::validate(payload, false).is_err());
+}
@@ -0,0 +1,67 @@
+#![cfg(all(feature = "recorder", feature = "exporter", feature = "debugging"))]
+
+use anyhow::Result;
+use metrics::{counter, gauge, histogram, with_local_recorder};
+use metrics_datadog_exporter::debugging::{from_snapshot, to_snapshot_entry};
+use metrics_datadog_exporter::{DataDogBuilder, DataDogMetric};
+use metrics_util::debugging::DebuggingRecorder;
+
+fn record() {
+    counter!("requests", "route" => "/").increment(2);
+    gauge!("connections").set(3.0);
+    histogram!("latency").record(1.5);
+    histogram!("latency").record(2.5);
+}
+
+fn sorted(mut metrics: Vec<DataDogMetric>) -> Vec<DataDogMetric> {
+    for metric in &mut metrics {
+        metric.timestamp = 0;
+    }
+    metrics.sort_by(|a, b| a.metric.cmp(&b.metric));
+    metrics
+}
+
+#[test]
+fn from_snapshot_test() -> Result<()> {
+    let tags = vec![("env".to_string(), "test".to_string())];
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .tags(tags)
+        .build()?;
+    with_local_recorder(&metrics.recorder, record);
+
+    let debugging = DebuggingRecorder::new();
+    let snapshotter = debugging.snapshotter();
+    with_local_recorder(&debugging, record);
+
+    let converted = from_snapshot(snapshotter.snapshot(), &["env:test".to_string()]);
+    assert_eq!(sorted(converted), sorted(metrics.handle.collect()));
+    Ok(())
+}
+
+#[test]
+fn to_snapshot_entry_test() -> Result<()> {
+    let debugging = DebuggingRecorder::new();
+    let snapshotter = debugging.snapshotter();
+    with_local_recorder(&debugging, record);
+    let mut snapshot = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key, value))
+        .collect::<Vec<_>>();
+
+    let metrics = DataDogBuilder::default().write_to_stdout(false).build()?;
+    with_local_recorder(&metrics.recorder, record);
+    let mut entries = metrics
+        .handle
+        .collect()
+        .iter()
+        .map(to_snapshot_entry)
+        .collect::<Vec<_>>();
+
+    snapshot.sort_by(|a, b| a.0.key().name().cmp(b.0.key().name()));
+    entries.sort_by(|a, b| a.0.key().name().cmp(b.0.key().name()));
+    assert_eq!(entries, snapshot);
+    Ok(())
+}
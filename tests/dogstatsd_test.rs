@@ -0,0 +1,70 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use anyhow::Result;
+use metrics::{counter, gauge, with_local_recorder};
+use metrics_datadog_exporter::DataDogBuilder;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+#[tokio::test]
+async fn dogstatsd_udp_test() -> Result<()> {
+    let agent = UdpSocket::bind("127.0.0.1:0")?;
+    agent.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_dogstatsd(true, Some(agent.local_addr()?.to_string()))
+        .tags(vec![("env".to_string(), "test".to_string())])
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("requests").increment(3);
+        gauge!("queue.depth").set(1.5);
+    });
+    metrics.flush().await?;
+
+    let mut buffer = [0; 1500];
+    let len = agent.recv(&mut buffer)?;
+    let datagram = std::str::from_utf8(&buffer[..len])?;
+    assert_eq!(
+        datagram,
+        "requests:3|c|#env:test\nqueue.depth:1.5|g|#env:test"
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dogstatsd_socket_test() -> Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = std::env::temp_dir().join(format!("dsd-{}.socket", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let agent = UnixDatagram::bind(&path)?;
+    agent.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_dogstatsd_socket(true, Some(path.to_string_lossy().to_string()))
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(1));
+    metrics.flush().await?;
+    let mut buffer = [0; 8192];
+    let len = agent.recv(&mut buffer)?;
+    assert_eq!(&buffer[..len], b"requests:1|c");
+
+    // The agent restarting recreates its socket
+    drop(agent);
+    std::fs::remove_file(&path)?;
+    let agent = UnixDatagram::bind(&path)?;
+    agent.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    with_local_recorder(&metrics.recorder, || counter!("requests").increment(2));
+    metrics.flush().await?;
+    let len = agent.recv(&mut buffer)?;
+    assert_eq!(&buffer[..len], b"requests:2|c");
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
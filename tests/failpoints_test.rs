@@ -0,0 +1,53 @@
+#![cfg(all(feature = "recorder", feature = "failpoints"))]
+
+use anyhow::Result;
+use httpmock::Method::POST;
+use httpmock::MockServer;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::failpoints::{self, FailAction, Failpoint};
+use metrics_datadog_exporter::{DataDogBuilder, Error};
+
+// Failpoints are global, so every case runs in a single test
+#[tokio::test]
+async fn failpoints_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .build()?;
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/series");
+        then.status(202);
+    });
+
+    failpoints::set(Failpoint::Send, FailAction::Status(500));
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    let Err(Error::FlushFailed(errors)) = metrics.flush().await else {
+        panic!("Expected flush to fail");
+    };
+    assert_eq!(errors.failed(), 1);
+
+    failpoints::clear();
+    failpoints::set(Failpoint::Serialize, FailAction::Error);
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    assert!(matches!(
+        metrics.flush().await,
+        Err(Error::Injected(FailAction::Error))
+    ));
+
+    failpoints::clear();
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    metrics.flush().await?;
+    mock.assert_hits(1);
+
+    failpoints::clear();
+    failpoints::set(Failpoint::Compress, FailAction::Error);
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    metrics.flush().await?;
+    mock.assert_hits(2);
+    assert_eq!(metrics.handle.status().compression_fallbacks, 1);
+    failpoints::clear();
+    Ok(())
+}
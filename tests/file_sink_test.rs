@@ -0,0 +1,83 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::data::DataDogMetricLine;
+use metrics_datadog_exporter::{DataDogBuilder, DataDogFileSink};
+
+/// Empty directory for the files of `test`
+fn directory(test: &str) -> Result<PathBuf> {
+    let directory = std::env::temp_dir().join(format!("dd-{test}-{}", std::process::id()));
+    fs::remove_dir_all(&directory).ok();
+    fs::create_dir_all(&directory)?;
+    Ok(directory)
+}
+
+fn lines(contents: &str) -> Result<Vec<DataDogMetricLine>> {
+    Ok(contents
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?)
+}
+
+#[tokio::test]
+async fn file_sink_test() -> Result<()> {
+    let directory = directory("file-sink")?;
+    let path = directory.join("metrics.jsonl");
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .sink(DataDogFileSink::new(&path))
+        .build()?;
+
+    for _ in 0..2 {
+        with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+        metrics.flush().await?;
+    }
+
+    let lines = lines(&fs::read_to_string(&path)?)?;
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|line| line.name == "metric"));
+    assert_eq!(fs::read_dir(&directory)?.count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_sink_rotation_test() -> Result<()> {
+    let directory = directory("file-sink-rotation")?;
+    let path = directory.join("metrics.jsonl");
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .sink(
+            DataDogFileSink::new(&path)
+                .max_bytes(1)
+                .max_age(Duration::from_secs(3600))
+                .gzip_rotated(true),
+        )
+        .build()?;
+
+    // Every flush exceeds the size limit, the previous one is rotated before writing
+    for _ in 0..3 {
+        with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+        metrics.flush().await?;
+    }
+
+    let mut rotated = fs::read_dir(&directory)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    rotated.retain(|rotated| *rotated != path);
+    assert_eq!(rotated.len(), 2);
+    for rotated in rotated {
+        assert_eq!(rotated.extension().unwrap_or_default(), "gz");
+        let mut contents = String::new();
+        GzDecoder::new(fs::File::open(rotated)?).read_to_string(&mut contents)?;
+        assert_eq!(lines(&contents)?.len(), 1);
+    }
+    assert_eq!(lines(&fs::read_to_string(&path)?)?.len(), 1);
+    Ok(())
+}
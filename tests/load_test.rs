@@ -0,0 +1,25 @@
+#![cfg(feature = "bench")]
+
+use anyhow::Result;
+use metrics_datadog_exporter::bench::{generate, LoadConfig};
+use metrics_datadog_exporter::DataDogBuilder;
+use std::time::Duration;
+
+#[tokio::test]
+async fn load_generator_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().build()?;
+    let config = LoadConfig {
+        series: 10,
+        points_per_second: 100,
+        churn: 0.5,
+        metric: "load".to_string(),
+    };
+
+    let report = generate(Some(&metrics.recorder), &config, Duration::from_secs(1)).await;
+
+    let collected = metrics.handle.collect();
+    assert!(report.points > 0);
+    assert!(collected.len() > config.series);
+    assert!(collected.len() as u64 <= report.series);
+    Ok(())
+}
@@ -0,0 +1,173 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use anyhow::Result;
+use httpmock::Method::PUT;
+use httpmock::MockServer;
+use metrics::{counter, describe_counter, with_local_recorder, Unit};
+use metrics_datadog_exporter::{
+    DataDogBuilder, DataDogMetadata, DataDogMetricType, DataDogUnit, Error,
+};
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::test]
+async fn sync_metadata_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .app_key("APP".to_string())
+        .api_host(server.base_url())
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!("requests", Unit::Count, "Requests served");
+    });
+    let mock = server.mock(|when, then| {
+        when.method(PUT)
+            .path("/metrics/requests")
+            .header("DD-APPLICATION-KEY", "APP")
+            .json_body_partial(
+                json!({"type": "count", "description": "Requests served"}).to_string(),
+            );
+        then.status(200);
+    });
+
+    assert_eq!(metrics.handle.sync_metadata().await?, 1);
+    mock.assert();
+
+    // Unchanged metadata isn't pushed again
+    assert_eq!(metrics.handle.sync_metadata().await?, 0);
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!(
+            "requests",
+            Unit::Count,
+            "Requests served, including retries"
+        );
+    });
+    let updated = server.mock(|when, then| {
+        when.method(PUT)
+            .path("/metrics/requests")
+            .json_body_partial(
+                json!({"description": "Requests served, including retries"}).to_string(),
+            );
+        then.status(200);
+    });
+    assert_eq!(metrics.handle.sync_metadata().await?, 1);
+    updated.assert();
+    Ok(())
+}
+
+#[tokio::test]
+async fn metadata_sync_interval_zero_test() -> Result<()> {
+    let server = MockServer::start();
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .app_key("APP".to_string())
+        .api_host(server.base_url())
+        .metadata_sync_interval(Duration::ZERO)
+        .build()?;
+    let mock = server.mock(|when, then| {
+        when.method(PUT).path("/metrics/requests");
+        then.status(200);
+    });
+
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!("requests", Unit::Count, "Requests served");
+    });
+    let (exporter, handle) = metrics.handle.schedule(Duration::from_secs(3600));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    mock.assert();
+
+    // The flush task is still running, so it stops on shutdown instead of having panicked
+    exporter.shutdown().await?;
+    handle.await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_metadata_without_app_key_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .build()?;
+
+    let result = metrics.handle.sync_metadata().await;
+    assert!(matches!(result, Err(Error::MissingAppKey)));
+    Ok(())
+}
+
+#[test]
+fn unit_tags_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().unit_tags(true).build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!("sent", Unit::Bytes, "Bytes sent");
+        counter!("sent").increment(1);
+        counter!("undescribed").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    let sent = collected.iter().find(|m| m.metric == "sent").unwrap();
+    assert_eq!(sent.tags, vec!["unit:byte".to_string()]);
+    let undescribed = collected
+        .iter()
+        .find(|m| m.metric == "undescribed")
+        .unwrap();
+    assert!(undescribed.tags.is_empty());
+    Ok(())
+}
+
+#[test]
+fn datadog_unit_test() {
+    assert_eq!(DataDogUnit::from(Unit::Mebibytes).unit(), "mebibyte");
+    assert_eq!(
+        DataDogUnit::from(Unit::BitsPerSecond).tag_value(),
+        "bit_per_second"
+    );
+    let custom = DataDogUnit::Custom {
+        unit: "request".to_string(),
+        per_unit: Some("second".to_string()),
+    };
+    assert_eq!(custom.per_unit(), Some("second"));
+}
+
+#[test]
+fn metadata_override_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .unit_tags(true)
+        .describe(
+            "library.bytes".to_string(),
+            DataDogMetadata {
+                metric_type: DataDogMetricType::Count,
+                unit: Some(Unit::Bytes.into()),
+                description: "Bytes read by the library".to_string(),
+            },
+        )
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!("library.bytes", Unit::Kibibytes, "Kibibytes read");
+        counter!("library.bytes").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    assert_eq!(collected[0].tags, vec!["unit:byte".to_string()]);
+    Ok(())
+}
+
+#[test]
+fn metadata_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        describe_counter!("requests", Unit::Count, "Requests served");
+    });
+
+    let metadata = metrics.handle.metadata();
+    assert_eq!(metadata["requests"].metric_type, DataDogMetricType::Count);
+    assert_eq!(metadata["requests"].description, "Requests served");
+    Ok(())
+}
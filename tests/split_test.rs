@@ -0,0 +1,85 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use anyhow::Result;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::data::{DataDogSeries, MAX_SERIES_POINTS};
+use metrics_datadog_exporter::{
+    DataDogBuilder, DataDogMetric, DataDogMetricType, DataDogMetricValue, DataDogSeriesType, Error,
+};
+
+#[tokio::test]
+async fn oversized_series_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .gzip(false)
+        .build()?;
+
+    let value = "x".repeat(4_000_000);
+    with_local_recorder(&metrics.recorder, || {
+        counter!("oversized", "tag" => value).increment(1);
+    });
+
+    let result = metrics.flush().await;
+    assert!(matches!(result, Err(Error::SeriesTooLarge { .. })));
+    Ok(())
+}
+
+#[test]
+fn split_points_test() {
+    let series = DataDogSeries {
+        interval: None,
+        metric: "metric".to_string(),
+        points: vec![
+            (1, DataDogMetricValue::Float(1.0)),
+            (1, DataDogMetricValue::Float(2.0)),
+            (1, DataDogMetricValue::Float(3.0)),
+        ],
+        tags: vec!["tag:value".to_string()],
+        metric_type: DataDogSeriesType::Gauge,
+        host: None,
+    };
+
+    let (left, right) = series.split_points().unwrap();
+    assert_eq!(left.points, vec![(1, DataDogMetricValue::Float(1.0))]);
+    assert_eq!(right.points.len(), 2);
+    assert_eq!(right.tags, series.tags);
+    assert!(left.split_points().is_none());
+}
+
+#[test]
+fn shard_test() {
+    let series = |tag: &str| DataDogSeries {
+        interval: None,
+        metric: "metric".to_string(),
+        points: vec![(1, DataDogMetricValue::Unsigned(1))],
+        tags: vec![tag.to_string()],
+        metric_type: DataDogSeriesType::Count,
+        host: None,
+    };
+
+    assert_eq!(series("tag:a").shard(8), series("tag:a").shard(8));
+    assert!((0..16).all(|i| series(&format!("tag:{i}")).shard(8) < 8));
+    assert_eq!(series("tag:a").shard(1), 0);
+}
+
+#[test]
+fn series_point_limit_test() {
+    let metric = DataDogMetric {
+        metric: "chatty".to_string(),
+        metric_type: DataDogMetricType::Histogram,
+        points: (0..MAX_SERIES_POINTS * 2 + 1)
+            .map(|value| DataDogMetricValue::Unsigned(value as u64))
+            .collect(),
+        timestamp: 1,
+        tags: ["tag:value".to_string()].into(),
+    };
+
+    let series = DataDogSeries::new(metric);
+    let points = series.iter().map(|s| s.points.len()).collect::<Vec<_>>();
+    assert_eq!(points, [MAX_SERIES_POINTS, MAX_SERIES_POINTS, 1]);
+    assert!(series
+        .iter()
+        .all(|s| s.metric == "chatty" && s.tags == ["tag:value"]));
+    assert_eq!(series[1].points[0].1, DataDogMetricValue::Unsigned(1000));
+}
@@ -0,0 +1,220 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use anyhow::Result;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::{DataDogBuilder, DataDogPreset, DataDogSite, Error};
+
+#[test]
+fn missing_api_key_test() {
+    let result = DataDogBuilder::default().write_to_api(true, None).build();
+    assert!(matches!(result, Err(Error::MissingApiKey)));
+}
+
+#[cfg(not(feature = "stdout"))]
+#[test]
+fn stdout_disabled_test() {
+    assert!(DataDogBuilder::default().build().is_ok());
+    let result = DataDogBuilder::default().write_to_stdout(true).build();
+    assert!(matches!(result, Err(Error::StdoutDisabled)));
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+#[test]
+fn tls_config_test() {
+    let result = DataDogBuilder::default()
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .add_root_certificate(
+            b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n".to_vec(),
+        )
+        .build();
+    assert!(matches!(result, Err(Error::ApiError(_))));
+
+    let result = DataDogBuilder::default()
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .client_identity(b"not an identity".to_vec())
+        .build();
+    assert!(matches!(result, Err(Error::ApiError(_))));
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+#[test]
+fn tls_disabled_test() {
+    let result = DataDogBuilder::default()
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .client_identity(vec![])
+        .build();
+    assert!(matches!(result, Err(Error::TlsDisabled)));
+}
+
+#[test]
+fn preset_test() {
+    let result = DataDogBuilder::default()
+        .write_to_api(true, None)
+        .preset(DataDogPreset::Development)
+        .build();
+    assert!(result.is_ok());
+
+    let result = DataDogBuilder::default()
+        .preset(DataDogPreset::Development)
+        .write_to_api(true, None)
+        .build();
+    assert!(matches!(result, Err(Error::MissingApiKey)));
+}
+
+#[test]
+fn site_test() -> Result<()> {
+    assert_eq!(
+        "datadoghq.eu".parse::<DataDogSite>()?.api_host(),
+        "https://api.datadoghq.eu/api/v1"
+    );
+    assert_eq!("ddog-gov.com".parse::<DataDogSite>()?, DataDogSite::US1Fed);
+    assert!(matches!(
+        "example.com".parse::<DataDogSite>(),
+        Err(Error::UnknownSite { site }) if site == "example.com"
+    ));
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .site(DataDogSite::AP1)
+        .build()?;
+    assert_eq!(
+        metrics.handle.status().endpoints[0].host,
+        "https://api.ap1.datadoghq.com/api/v1"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn status_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host("http://127.0.0.1:1".to_string())
+        .build()?;
+    assert!(!metrics.handle.status().api_misconfigured);
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("status.counter").increment(1);
+    });
+    assert!(metrics.flush().await.is_err());
+
+    let status = metrics.handle.status();
+    assert_eq!(status.consecutive_failures, 1);
+    assert!(status.last_error.is_some());
+    assert!(status.last_success.is_none());
+
+    metrics.flush().await?;
+    let status = metrics.handle.status();
+    assert_eq!(status.consecutive_failures, 0);
+    assert!(status.last_success.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn series_budget_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .max_series(2)
+        .high_priority(vec!["slo".to_string()])
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("debug.a").increment(1);
+        counter!("debug.b").increment(1);
+        counter!("slo.errors").increment(1);
+    });
+    metrics.flush().await?;
+
+    assert_eq!(metrics.handle.status().dropped_series, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn memory_budget_test() -> Result<()> {
+    let record = || {
+        counter!("debug.a").increment(1);
+        counter!("debug.b").increment(1);
+        counter!("slo.errors").increment(1);
+    };
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .memory_budget(1 << 20)
+        .build()?;
+    with_local_recorder(&metrics.recorder, record);
+    metrics.flush().await?;
+    let memory = metrics.handle.status().memory.unwrap();
+    assert!(memory.usage > 0 && memory.pressure < 0.8);
+    assert_eq!(memory.shed_series, 0);
+    assert_eq!(metrics.handle.status().dropped_series, 0);
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .memory_budget(1)
+        .build()?;
+    with_local_recorder(&metrics.recorder, record);
+    metrics.flush().await?;
+    let memory = metrics.handle.status().memory.unwrap();
+    assert!(memory.pressure > 1.0);
+    assert_eq!(memory.shed_series, 3);
+    assert_eq!(metrics.handle.status().dropped_series, 3);
+
+    let pressure = metrics
+        .handle
+        .collect()
+        .into_iter()
+        .find(|metric| metric.metric == "datadog.exporter.memory.pressure");
+    assert!(pressure.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn checkpoint_on_crash_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .checkpoint_on_crash(true)
+        .build()?;
+    with_local_recorder(&metrics.recorder, || counter!("checkpointed").increment(1));
+    metrics.flush().await?;
+    with_local_recorder(&metrics.recorder, || counter!("pending").increment(1));
+
+    // The last flush is written to stderr, metrics recorded since are kept
+    assert!(std::panic::catch_unwind(|| panic!("crash")).is_err());
+    let collected = metrics.handle.collect();
+    assert_eq!(collected.len(), 1);
+    assert_eq!(collected[0].metric, "pending");
+    Ok(())
+}
+
+#[tokio::test]
+async fn stage_timings_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .stage_timings(true)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("metric").increment(1));
+    metrics.flush().await?;
+
+    let stats = metrics.handle.status().last_flush.unwrap();
+    assert_eq!(stats.metrics, 1);
+    assert_eq!(stats.payloads, 0);
+
+    let mut timings = metrics
+        .handle
+        .collect()
+        .into_iter()
+        .map(|metric| metric.metric)
+        .collect::<Vec<_>>();
+    timings.sort();
+    assert_eq!(
+        timings,
+        [
+            "datadog.exporter.flush.collect",
+            "datadog.exporter.flush.encode",
+            "datadog.exporter.flush.send",
+            "datadog.exporter.flush.transform",
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,215 @@
+#![cfg(all(feature = "recorder", feature = "exporter"))]
+
+use anyhow::Result;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::{
+    with_build_info, DataDogBuilder, DataDogTagFilter, DataDogTags, DataDogValidation, Error,
+};
+
+#[test]
+fn tag_template_test() -> Result<()> {
+    std::env::set_var("TAGS_TEST_POD", "pod-1");
+    let metrics = DataDogBuilder::default()
+        .tags(vec![
+            ("pod".to_string(), "{env:TAGS_TEST_POD}".to_string()),
+            ("build".to_string(), "{build}-x".to_string()),
+        ])
+        .tag_context("build".to_string(), "abc".to_string())
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    assert_eq!(
+        collected[0].tags,
+        vec!["pod:pod-1".to_string(), "build:abc-x".to_string()]
+    );
+
+    // Escaped braces resolve, but DataDog rewrites them
+    let result = DataDogBuilder::default()
+        .tags(vec![("build".to_string(), "{{x}}".to_string())])
+        .build();
+    assert!(matches!(result, Err(Error::InvalidTag { tag, .. }) if tag == "build:{x}"));
+    Ok(())
+}
+
+#[test]
+fn from_env_test() -> Result<()> {
+    std::env::set_var("DD_API_KEY", "DUMMY");
+    // Tags without a value are skipped
+    std::env::set_var(
+        "DD_TAGS",
+        "solo,team:payments,env:staging zone:us-east-1a bare:",
+    );
+    std::env::set_var("DD_ENV", "prod");
+    std::env::set_var("DD_SERVICE", "checkout");
+    let metrics = DataDogBuilder::from_env().write_to_stdout(false).build()?;
+    for var in ["DD_API_KEY", "DD_TAGS", "DD_ENV", "DD_SERVICE"] {
+        std::env::remove_var(var);
+    }
+    assert!(!metrics.handle.status().api_misconfigured);
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    assert_eq!(
+        collected[0].tags,
+        vec![
+            "team:payments".to_string(),
+            "zone:us-east-1a".to_string(),
+            "env:prod".to_string(),
+            "service:checkout".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn unresolved_tag_template_test() {
+    let result = DataDogBuilder::default()
+        .tags(vec![("pod".to_string(), "{unknown}".to_string())])
+        .build();
+    assert!(
+        matches!(result, Err(Error::UnresolvedTag { placeholder, .. }) if placeholder == "unknown")
+    );
+}
+
+#[test]
+fn typed_tags_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .tags(
+            DataDogTags::new()
+                .env("prod")
+                .service("checkout")
+                .region("eu-west-1")
+                .custom("team", "payments"),
+        )
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    assert_eq!(
+        collected[0].tags,
+        vec![
+            "env:prod".to_string(),
+            "service:checkout".to_string(),
+            "region:eu-west-1".to_string(),
+            "team:payments".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_tag_test() {
+    let tags = DataDogTags::new().custom("env=prod", "");
+    assert!(matches!(tags.validate(), Err(Error::InvalidTag { .. })));
+
+    let result = DataDogBuilder::default().tags(tags).build();
+    assert!(matches!(result, Err(Error::InvalidTag { tag, .. }) if tag == "env=prod:"));
+
+    let result = DataDogBuilder::default()
+        .namespace_tags(
+            "kafka".to_string(),
+            DataDogTags::new().custom("team", "a,b"),
+        )
+        .build();
+    assert!(matches!(result, Err(Error::InvalidTag { .. })));
+}
+
+#[test]
+fn build_info_tags_test() -> Result<()> {
+    let metrics = with_build_info!(DataDogBuilder::default()).build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("metric").increment(1);
+    });
+
+    let tags = &metrics.handle.collect()[0].tags;
+    assert!(tags.contains(&format!("version:{}", env!("CARGO_PKG_VERSION"))));
+    assert!(tags.iter().any(|tag| tag.starts_with("profile:")));
+    Ok(())
+}
+
+#[test]
+fn namespace_tags_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .namespace_tags(
+            "kafka".to_string(),
+            vec![
+                ("component".to_string(), "kafka".to_string()),
+                ("team".to_string(), "data".to_string()),
+            ],
+        )
+        .namespace_tags(
+            "kafka.consumer".to_string(),
+            vec![("team".to_string(), "ingest".to_string())],
+        )
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("kafka.consumer.lag", "component" => "custom").increment(1);
+        counter!("kafkaesque").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    let lag = collected
+        .iter()
+        .find(|m| m.metric == "kafka.consumer.lag")
+        .unwrap();
+    assert_eq!(
+        lag.tags,
+        vec!["team:ingest".to_string(), "component:custom".to_string()]
+    );
+    let other = collected.iter().find(|m| m.metric == "kafkaesque").unwrap();
+    assert!(other.tags.is_empty());
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "must start with a letter")]
+fn validation_panic_test() {
+    let metrics = DataDogBuilder::default()
+        .validation(DataDogValidation::Panic)
+        .build()
+        .unwrap();
+
+    with_local_recorder(&metrics.recorder, || counter!("9lives").increment(1));
+}
+
+#[test]
+fn tag_filter_test() -> Result<()> {
+    let metrics = DataDogBuilder::default()
+        .tags(vec![("env".to_string(), "prod".to_string())])
+        .tag_filter(DataDogTagFilter::Drop {
+            tag: "endpoint".to_string(),
+            values: vec!["/healthz".to_string()],
+        })
+        .tag_filter(DataDogTagFilter::Keep {
+            tag: "env".to_string(),
+            values: vec!["prod".to_string()],
+        })
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("requests", "endpoint" => "/healthz").increment(1);
+        counter!("requests", "endpoint" => "/users").increment(1);
+        counter!("requests", "endpoint" => "/users", "env" => "dev").increment(1);
+    });
+
+    let collected = metrics.handle.collect();
+    assert_eq!(collected.len(), 1);
+    assert_eq!(
+        collected[0].tags,
+        vec!["env:prod".to_string(), "endpoint:/users".to_string()]
+    );
+    assert!(metrics.handle.collect().is_empty());
+    Ok(())
+}